@@ -0,0 +1,269 @@
+use crate::sensors::{TempHum, TempHumSensor};
+use core::fmt;
+use embedded_hal::delay::DelayUs;
+use embedded_hal::i2c::I2c;
+
+/// Address with SDO tied to ground, with SDO tied to VDDIO it is 0x77
+pub const DEFAULT_ADDRESS: u8 = 0x76;
+
+const REG_CALIB_00: u8 = 0x88;
+const REG_CHIP_ID: u8 = 0xd0;
+const REG_RESET: u8 = 0xe0;
+const REG_CALIB_26: u8 = 0xe1;
+const REG_CTRL_HUM: u8 = 0xf2;
+const REG_STATUS: u8 = 0xf3;
+const REG_CTRL_MEAS: u8 = 0xf4;
+const REG_DATA: u8 = 0xf7;
+
+const CHIP_ID_BMP280: u8 = 0x58;
+const CHIP_ID_BME280: u8 = 0x60;
+const RESET_COMMAND: u8 = 0xb6;
+/// conversion running
+const STATUS_MEASURING: u8 = 0x08;
+/// 1x oversampling of the temperature (bits 7..5) and pressure (bits 4..2)
+/// in forced mode (bits 1..0)
+const CTRL_MEAS_FORCED: u8 = 0b0010_0101;
+/// 1x oversampling of the humidity
+const CTRL_HUM_OVERSAMPLING: u8 = 0b001;
+/// a conversion with 1x oversampling takes 9.3 ms at most
+const MEASUREMENT_MS: u32 = 10;
+
+#[derive(Debug)]
+pub enum Bme280Error<HE> {
+    /// the chip id is neither the one of the BME280 nor of the BMP280
+    UnknownChip(u8),
+    /// the conversion did not finish in time
+    Busy,
+    /// Error of underlying IO
+    HalError(HE),
+}
+
+impl<HE> From<HE> for Bme280Error<HE> {
+    fn from(error: HE) -> Self {
+        Bme280Error::HalError(error)
+    }
+}
+
+impl<HE: fmt::Debug> fmt::Display for Bme280Error<HE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Bme280Error::*;
+        match self {
+            UnknownChip(id) => write!(f, "Unknown chip id: {:x}", id),
+            Busy => write!(f, "Measurement not finished"),
+            HalError(err) => write!(f, "HAL error: {:?}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<HE: fmt::Debug> std::error::Error for Bme280Error<HE> {}
+
+/// The BMP280 is the same sensor without the humidity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip {
+    Bme280,
+    Bmp280,
+}
+
+/// Trimming parameters stored in the sensor during production
+#[derive(Debug, Clone, Copy)]
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+impl Calibration {
+    /// Parses the registers 0x88..0xa1 and 0xe1..0xe7, the latter are only
+    /// present on the BME280
+    fn parse(regs: &[u8; 26], humidity_regs: &[u8; 7]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([regs[i], regs[i + 1]]);
+        let i16_at = |i: usize| i16::from_le_bytes([regs[i], regs[i + 1]]);
+        Self {
+            t1: u16_at(0),
+            t2: i16_at(2),
+            t3: i16_at(4),
+            p1: u16_at(6),
+            p2: i16_at(8),
+            p3: i16_at(10),
+            p4: i16_at(12),
+            p5: i16_at(14),
+            p6: i16_at(16),
+            p7: i16_at(18),
+            p8: i16_at(20),
+            p9: i16_at(22),
+            h1: regs[25],
+            h2: i16::from_le_bytes([humidity_regs[0], humidity_regs[1]]),
+            h3: humidity_regs[2],
+            // 12 bit values sharing the nibbles of 0xe5
+            h4: (humidity_regs[3] as i8 as i16) << 4 | (humidity_regs[4] & 0x0f) as i16,
+            h5: (humidity_regs[5] as i8 as i16) << 4 | (humidity_regs[4] >> 4) as i16,
+            h6: humidity_regs[6] as i8,
+        }
+    }
+
+    /// Returns the fine temperature used by the other compensations and the
+    /// temperature in degree celsius, the formulas are the ones of the datasheet
+    fn temperature(&self, adc: i32) -> (f64, f64) {
+        let adc = adc as f64;
+        let t1 = self.t1 as f64;
+        let var1 = (adc / 16384.0 - t1 / 1024.0) * self.t2 as f64;
+        let var2 = (adc / 131072.0 - t1 / 8192.0) * (adc / 131072.0 - t1 / 8192.0) * self.t3 as f64;
+        let t_fine = var1 + var2;
+        (t_fine, t_fine / 5120.0)
+    }
+
+    /// Returns the pressure in Pa
+    fn pressure(&self, t_fine: f64, adc: i32) -> f64 {
+        let mut var1 = t_fine / 2.0 - 64000.0;
+        let mut var2 = var1 * var1 * self.p6 as f64 / 32768.0;
+        var2 += var1 * self.p5 as f64 * 2.0;
+        var2 = var2 / 4.0 + self.p4 as f64 * 65536.0;
+        var1 = (self.p3 as f64 * var1 * var1 / 524288.0 + self.p2 as f64 * var1) / 524288.0;
+        var1 = (1.0 + var1 / 32768.0) * self.p1 as f64;
+        if var1 == 0.0 {
+            // avoid the division by zero of an unprogrammed sensor
+            return 0.0;
+        }
+        let mut pressure = 1048576.0 - adc as f64;
+        pressure = (pressure - var2 / 4096.0) * 6250.0 / var1;
+        var1 = self.p9 as f64 * pressure * pressure / 2147483648.0;
+        var2 = pressure * self.p8 as f64 / 32768.0;
+        pressure + (var1 + var2 + self.p7 as f64) / 16.0
+    }
+
+    /// Returns the relative humidity in the range of 0..100%
+    fn humidity(&self, t_fine: f64, adc: i32) -> f64 {
+        let var = t_fine - 76800.0;
+        let var = (adc as f64 - (self.h4 as f64 * 64.0 + self.h5 as f64 / 16384.0 * var))
+            * (self.h2 as f64 / 65536.0
+                * (1.0
+                    + self.h6 as f64 / 67108864.0
+                        * var
+                        * (1.0 + self.h3 as f64 / 67108864.0 * var)));
+        let var = var * (1.0 - self.h1 as f64 * var / 524288.0);
+        var.clamp(0.0, 100.0)
+    }
+}
+
+/// Bosch BME280 measuring the temperature, humidity and pressure, or the
+/// BMP280 without the humidity. The sensor sleeps between the reads, every
+/// read triggers a single conversion.
+pub struct Bme280<HE, I: I2c<Error = HE>> {
+    i2c: I,
+    address: u8,
+    /// detected and calibrated on the first read, so a sensor connected
+    /// later is picked up as well
+    calibration: Option<(Chip, Calibration)>,
+}
+
+impl<HE, I: I2c<Error = HE>> Bme280<HE, I> {
+    pub fn new(i2c: I, address: u8) -> Self {
+        Self {
+            i2c,
+            address,
+            calibration: None,
+        }
+    }
+
+    /// Returns the detected chip, `None` before the first successful read
+    pub fn chip(&self) -> Option<Chip> {
+        self.calibration.map(|(chip, _)| chip)
+    }
+
+    /// Resets the sensor and reads its calibration
+    fn init(&mut self, delay: &mut impl DelayUs) -> Result<(Chip, Calibration), Bme280Error<HE>> {
+        let mut id = [0u8; 1];
+        self.i2c.write_read(self.address, &[REG_CHIP_ID], &mut id)?;
+        let chip = match id[0] {
+            CHIP_ID_BME280 => Chip::Bme280,
+            CHIP_ID_BMP280 => Chip::Bmp280,
+            id => return Err(Bme280Error::UnknownChip(id)),
+        };
+        self.i2c.write(self.address, &[REG_RESET, RESET_COMMAND])?;
+        // the calibration is copied from the nvm within 2 ms after the reset
+        delay.delay_ms(2);
+
+        let mut regs = [0u8; 26];
+        self.i2c
+            .write_read(self.address, &[REG_CALIB_00], &mut regs)?;
+        let mut humidity_regs = [0u8; 7];
+        if chip == Chip::Bme280 {
+            self.i2c
+                .write_read(self.address, &[REG_CALIB_26], &mut humidity_regs)?;
+        }
+        Ok((chip, Calibration::parse(&regs, &humidity_regs)))
+    }
+
+    /// Runs a single conversion and returns the compensated values, the
+    /// pressure in hPa
+    pub fn read_measurement(
+        &mut self,
+        delay: &mut impl DelayUs,
+    ) -> Result<TempHum, Bme280Error<HE>> {
+        let (chip, calibration) = match self.calibration {
+            Some(calibration) => calibration,
+            None => {
+                let calibration = self.init(delay)?;
+                self.calibration = Some(calibration);
+                calibration
+            }
+        };
+
+        // the humidity settings only apply after writing ctrl_meas
+        if chip == Chip::Bme280 {
+            self.i2c
+                .write(self.address, &[REG_CTRL_HUM, CTRL_HUM_OVERSAMPLING])?;
+        }
+        self.i2c
+            .write(self.address, &[REG_CTRL_MEAS, CTRL_MEAS_FORCED])?;
+        delay.delay_ms(MEASUREMENT_MS);
+        let mut status = [0u8; 1];
+        self.i2c
+            .write_read(self.address, &[REG_STATUS], &mut status)?;
+        if status[0] & STATUS_MEASURING != 0 {
+            return Err(Bme280Error::Busy);
+        }
+
+        // pressure and temperature in 20 bits, humidity in 16 bits
+        let mut data = [0u8; 8];
+        let len = if chip == Chip::Bme280 { 8 } else { 6 };
+        self.i2c
+            .write_read(self.address, &[REG_DATA], &mut data[..len])?;
+        let adc_20 = |i: usize| {
+            (data[i] as i32) << 12 | (data[i + 1] as i32) << 4 | (data[i + 2] as i32) >> 4
+        };
+        let (t_fine, temperature) = calibration.temperature(adc_20(3));
+        let pressure = calibration.pressure(t_fine, adc_20(0));
+        let humidity = (chip == Chip::Bme280)
+            .then(|| calibration.humidity(t_fine, (data[6] as i32) << 8 | data[7] as i32));
+        Ok(TempHum {
+            temperature: temperature as f32,
+            humidity: humidity.map(|humidity| humidity as f32),
+            pressure: Some((pressure / 100.0) as f32),
+        })
+    }
+}
+
+impl<HE, I: I2c<Error = HE>> TempHumSensor for Bme280<HE, I> {
+    type Error = Bme280Error<HE>;
+
+    fn read_temp_hum(&mut self, mut delay: &mut dyn DelayUs) -> Result<TempHum, Bme280Error<HE>> {
+        self.read_measurement(&mut delay)
+    }
+}
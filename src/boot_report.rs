@@ -0,0 +1,70 @@
+use crate::diagnostics::MemoryStats;
+use crate::payload::JsonStr;
+use crate::power::PowerHistory;
+use crate::selftest::SelfTestReport;
+use core::fmt::{self, Write as _};
+use esp_idf_svc::hal::reset::ResetReason;
+
+/// Summary of the device state published once after startup
+pub struct BootReport {
+    pub reset_reason: ResetReason,
+    pub sensors: SelfTestReport,
+    pub config_checksum: u32,
+    pub time_synced: bool,
+    pub power: PowerHistory,
+    /// reason of the panic that ended the previous run
+    pub last_panic: Option<String>,
+    pub memory: MemoryStats,
+}
+
+impl BootReport {
+    /// Returns the report as json object
+    pub fn to_json(&self) -> String {
+        let last_panic = match &self.last_panic {
+            Some(reason) => format!("\"{}\"", JsonStr(reason)),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"firmware\": \"{}\", \"reset_reason\": \"{:?}\", \"sensors\": {}, \"config_checksum\": \"{:08x}\", \"time_synced\": {}, \"power\": {}, \"last_panic\": {}, \"memory\": {}}}",
+            env!("CARGO_PKG_VERSION"),
+            self.reset_reason,
+            self.sensors.to_json(),
+            self.config_checksum,
+            self.time_synced,
+            self.power.to_json(),
+            last_panic,
+            self.memory
+        )
+    }
+}
+
+/// FNV-1a hash used to tell apart the configurations of the nodes, the
+/// values are added one by one, each followed by a separator
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigChecksum(u32);
+
+impl ConfigChecksum {
+    pub fn add(&mut self, value: impl fmt::Display) -> &mut Self {
+        let _ = write!(self, "{}|", value);
+        self
+    }
+
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for ConfigChecksum {
+    fn default() -> Self {
+        Self(0x811c9dc5)
+    }
+}
+
+impl fmt::Write for ConfigChecksum {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.0 = (self.0 ^ byte as u32).wrapping_mul(0x01000193);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use crate::clock::LocalTime;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Policy when the zero point of the MH-Z19 may be calibrated
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrationPolicy {
+    /// day of the week in days since sunday
+    pub weekday: u8,
+    /// local hour the calibration window starts
+    pub start_hour: u8,
+    /// local hour the calibration window ends
+    pub end_hour: u8,
+    /// how long the room has to be unoccupied before
+    pub unoccupied: Duration,
+    /// maximum difference between the co2 readings while unoccupied
+    pub max_spread: i32,
+}
+
+/// Decides when to run a zero point calibration. Without an occupancy sensor
+/// the room counts as unoccupied when the co2 concentration was stable.
+pub struct CalibrationScheduler {
+    policy: CalibrationPolicy,
+    readings: VecDeque<(Instant, i32)>,
+    last_calibration: Option<(i32, u8, u8)>,
+}
+
+impl CalibrationScheduler {
+    /// The buffer for the readings of the unoccupied period is allocated up front
+    /// for readings recorded every `interval`
+    pub fn new(policy: CalibrationPolicy, interval: Duration) -> Self {
+        let capacity = (policy.unoccupied.as_secs() / interval.as_secs().max(1)) as usize + 2;
+        Self {
+            policy,
+            readings: VecDeque::with_capacity(capacity),
+            last_calibration: None,
+        }
+    }
+
+    /// Records a co2 reading, readings older than the unoccupied period are dropped
+    pub fn record(&mut self, co2: i32) {
+        let now = Instant::now();
+        self.readings.push_back((now, co2));
+        while let Some((time, _)) = self.readings.front() {
+            if now.duration_since(*time) <= self.policy.unoccupied {
+                break;
+            }
+            self.readings.pop_front();
+        }
+    }
+
+    fn is_stable(&self) -> bool {
+        let covered = match (self.readings.front(), self.readings.back()) {
+            (Some((first, _)), Some((last, _))) => last.duration_since(*first),
+            _ => return false,
+        };
+        // allow one missed reading at the start of the period
+        if covered + Duration::from_secs(10 * 60) < self.policy.unoccupied {
+            return false;
+        }
+        let min = self.readings.iter().map(|(_, co2)| *co2).min().unwrap_or(0);
+        let max = self.readings.iter().map(|(_, co2)| *co2).max().unwrap_or(0);
+        max - min <= self.policy.max_spread
+    }
+
+    /// Returns true if the calibration should run now
+    pub fn is_due(&self, now: &LocalTime) -> bool {
+        now.weekday == self.policy.weekday
+            && (self.policy.start_hour..self.policy.end_hour).contains(&now.hour)
+            && self.last_calibration != Some((now.year, now.month, now.day))
+            && self.is_stable()
+    }
+
+    /// Marks the calibration of the current window as done
+    pub fn calibrated(&mut self, now: &LocalTime) {
+        self.last_calibration = Some((now.year, now.month, now.day));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::bme280::{Bme280, Bme280Error};
+use crate::dht22::DhtError;
+use crate::i2c_bus::SharedI2c;
+use crate::sensors::{TempHum, TempHumSensor};
+use crate::sht::{Precision, Sht3x, Sht4x, ShtError};
+use anyhow::Result;
+use core::cell::RefCell;
+use embedded_hal::delay::DelayUs;
+use esp_idf_svc::hal::i2c::{I2cDriver, I2cError};
+use esp_idf_svc::sys::EspError;
+
+/// The temperature and humidity sensor of the node, one of the DHT readers on
+/// the data pin, the BME280/BMP280 or one of the Sensirion sensors on the i2c bus
+pub enum ClimateSource<'a> {
+    Dht(Box<dyn TempHumSensor<Error = DhtError<EspError>> + 'a>),
+    Bme280(Box<dyn TempHumSensor<Error = Bme280Error<I2cError>> + 'a>),
+    Sht(
+        &'static str,
+        Box<dyn TempHumSensor<Error = ShtError<I2cError>> + 'a>,
+    ),
+}
+
+impl<'a> ClimateSource<'a> {
+    /// Returns the sensor of the given kind on the i2c bus, none for the DHT
+    pub fn on_i2c<'d: 'a>(
+        bus: &'a RefCell<I2cDriver<'d>>,
+        kind: &str,
+        bme280_address: u8,
+        sht_address: u8,
+        sht_precision: Precision,
+    ) -> Option<Self> {
+        match kind {
+            "bme280" => Some(ClimateSource::Bme280(Box::new(Bme280::new(
+                SharedI2c::new(bus),
+                bme280_address,
+            )))),
+            "sht3x" => Some(ClimateSource::Sht(
+                "sht3x",
+                Box::new(Sht3x::new(SharedI2c::new(bus), sht_address, sht_precision)),
+            )),
+            "sht4x" => Some(ClimateSource::Sht(
+                "sht4x",
+                Box::new(Sht4x::new(SharedI2c::new(bus), sht_address, sht_precision)),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Name of the sensor in the selftest report
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClimateSource::Dht(_) => "dht22",
+            ClimateSource::Bme280(_) => "bme280",
+            ClimateSource::Sht(name, _) => name,
+        }
+    }
+}
+
+impl TempHumSensor for ClimateSource<'_> {
+    type Error = anyhow::Error;
+
+    fn read_temp_hum(&mut self, delay: &mut dyn DelayUs) -> Result<TempHum> {
+        match self {
+            ClimateSource::Dht(dht) => Ok(dht.read_temp_hum(delay)?),
+            ClimateSource::Bme280(bme280) => Ok(bme280.read_temp_hum(delay)?),
+            ClimateSource::Sht(_, sht) => Ok(sht.read_temp_hum(delay)?),
+        }
+    }
+}
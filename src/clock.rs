@@ -0,0 +1,126 @@
+use anyhow::Result;
+use core::cell::Cell;
+use core::fmt;
+use esp_idf_svc::sntp::{EspSntp, SyncStatus};
+use esp_idf_svc::sys;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Wall clock synchronized via SNTP and converted to the configured time zone
+pub struct Clock {
+    sntp: EspSntp<'static>,
+    synced: Cell<bool>,
+    sntp_update: Cell<bool>,
+}
+
+impl Clock {
+    /// Starts the SNTP synchronization and applies the given POSIX time zone
+    /// string, e.g. `CET-1CEST,M3.5.0,M10.5.0/3` for central europe
+    pub fn new(timezone: &str) -> Result<Self> {
+        let sntp = EspSntp::new_default()?;
+        std::env::set_var("TZ", timezone);
+        unsafe { sys::tzset() };
+        Ok(Self {
+            sntp,
+            synced: Cell::new(false),
+            sntp_update: Cell::new(false),
+        })
+    }
+
+    fn poll_sntp(&self) {
+        // esp-idf resets the status after reporting a completed sync once
+        if self.sntp.get_sync_status() == SyncStatus::Completed {
+            self.synced.set(true);
+            self.sntp_update.set(true);
+        }
+    }
+
+    /// Returns true once the system time has been synchronized or set
+    pub fn is_synced(&self) -> bool {
+        self.poll_sntp();
+        self.synced.get()
+    }
+
+    /// Returns true once after every completed SNTP synchronization
+    pub fn take_sntp_update(&self) -> bool {
+        self.poll_sntp();
+        self.sntp_update.replace(false)
+    }
+
+    /// Sets the system time from another time source, e.g. a real time clock
+    pub fn set_time(&self, unix_time: u64) {
+        let time = sys::timeval {
+            tv_sec: unix_time as _,
+            tv_usec: 0,
+        };
+        unsafe { sys::settimeofday(&time, core::ptr::null()) };
+        self.synced.set(true);
+    }
+
+    /// Returns the current time in seconds since the unix epoch
+    pub fn unix_time(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+
+    /// Returns the current local time
+    pub fn now(&self) -> LocalTime {
+        LocalTime::now()
+    }
+
+    /// Returns the time until the wall clock reaches the next multiple of the
+    /// interval, e.g. the next full minute for an interval of 60 seconds
+    pub fn until_aligned(&self, interval: Duration) -> Duration {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let interval = interval.as_millis();
+        Duration::from_millis((interval - since_epoch % interval) as u64)
+    }
+}
+
+/// Local time broken down into its calendar fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: i32,
+    /// month of the year in the range of 1..=12
+    pub month: u8,
+    /// day of the month in the range of 1..=31
+    pub day: u8,
+    /// days since sunday in the range of 0..=6
+    pub weekday: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl LocalTime {
+    fn now() -> Self {
+        let mut tm: sys::tm = unsafe { core::mem::zeroed() };
+        unsafe {
+            let now = sys::time(core::ptr::null_mut());
+            sys::localtime_r(&now, &mut tm);
+        }
+        Self {
+            year: tm.tm_year + 1900,
+            month: (tm.tm_mon + 1) as u8,
+            day: tm.tm_mday as u8,
+            weekday: tm.tm_wday as u8,
+            hour: tm.tm_hour as u8,
+            minute: tm.tm_min as u8,
+            second: tm.tm_sec as u8,
+        }
+    }
+}
+
+impl fmt::Display for LocalTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
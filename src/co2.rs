@@ -0,0 +1,81 @@
+use crate::i2c_bus::SharedI2c;
+use crate::scd::{Scd30, Scd4x, ScdError};
+use crate::sensors::Co2Sensor;
+use anyhow::Result;
+use core::cell::RefCell;
+use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::hal::i2c::{I2cDriver, I2cError};
+
+/// The co2 sensor of the node, the MH-Z19 on the uart or one of the Sensirion
+/// sensors on the i2c bus
+pub enum Co2Source<'a, M> {
+    MHz19(M),
+    Sensirion(
+        &'static str,
+        Box<dyn Co2Sensor<Error = ScdError<I2cError>> + 'a>,
+    ),
+}
+
+impl<'a, M> Co2Source<'a, M> {
+    /// Starts the measurement of the Sensirion sensor of the given kind on the
+    /// i2c bus, none for the kinds on the uart
+    pub fn on_i2c<'d: 'a>(
+        bus: &'a RefCell<I2cDriver<'d>>,
+        kind: &str,
+        auto_calibration: bool,
+    ) -> Option<Self> {
+        match kind {
+            "scd30" => {
+                let mut scd30 = Scd30::new(SharedI2c::new(bus));
+                if let Err(err) = scd30.set_auto_calibration(auto_calibration) {
+                    log::warn!("error setting SCD30 auto calibration: {:}", err);
+                }
+                if let Err(err) = scd30.start_measurement(5) {
+                    log::warn!("error starting SCD30 measurement: {:}", err);
+                }
+                Some(Co2Source::Sensirion("scd30", Box::new(scd30)))
+            }
+            "scd4x" => {
+                let mut scd4x = Scd4x::new(SharedI2c::new(bus));
+                let started = scd4x
+                    .stop_measurement(&mut FreeRtos)
+                    .and_then(|_| scd4x.set_auto_calibration(auto_calibration))
+                    .and_then(|_| scd4x.start_measurement());
+                if let Err(err) = started {
+                    log::warn!("error starting SCD4x measurement: {:}", err);
+                }
+                Some(Co2Source::Sensirion("scd4x", Box::new(scd4x)))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<M: Co2Sensor> Co2Source<'_, M>
+where
+    M::Error: std::error::Error + Send + Sync + 'static,
+{
+    pub fn read_ppm(&mut self) -> Result<i32> {
+        match self {
+            Co2Source::MHz19(mhz19) => Ok(mhz19.read_ppm(&mut FreeRtos)?),
+            Co2Source::Sensirion(_, sensor) => Ok(sensor.read_ppm(&mut FreeRtos)?),
+        }
+    }
+
+    /// Name of the sensor in the selftest report
+    pub fn name(&self) -> &'static str {
+        match self {
+            Co2Source::MHz19(_) => "mhz19",
+            Co2Source::Sensirion(name, _) => name,
+        }
+    }
+
+    /// Returns the MH-Z19 for the commands only it supports, e.g. calibrations
+    #[cfg(any(feature = "console", feature = "scheduled-calibration"))]
+    pub fn mhz19(&mut self) -> Option<&mut M> {
+        match self {
+            Co2Source::MHz19(mhz19) => Some(mhz19),
+            Co2Source::Sensirion(..) => None,
+        }
+    }
+}
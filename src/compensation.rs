@@ -0,0 +1,60 @@
+use crate::measurement::Measurement;
+
+/// Conditions the MH-Z19 is calibrated at
+const REFERENCE_TEMPERATURE_C: f32 = 25.0;
+const REFERENCE_PRESSURE_HPA: f32 = 1013.25;
+const ZERO_CELSIUS_K: f32 = 273.15;
+
+/// How the co2 reading is corrected for the ambient conditions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compensation {
+    /// values are passed on unchanged
+    None,
+    /// the sensor counts the molecules in its cell, so the reading follows the
+    /// density of the air, which changes with the temperature and the pressure
+    /// by the ideal gas law. Without a measured pressure the reference
+    /// pressure is assumed.
+    IdealGas,
+    /// empirical correction in ppm per degree the temperature deviates from
+    /// the reference temperature
+    Linear(f32),
+}
+
+impl Compensation {
+    /// Parses the configured model `none`, `ideal_gas` or `linear`
+    pub fn parse(model: &str, ppm_per_degree: f32) -> Option<Self> {
+        match model {
+            "none" => Some(Compensation::None),
+            "ideal_gas" => Some(Compensation::IdealGas),
+            "linear" => Some(Compensation::Linear(ppm_per_degree)),
+            _ => None,
+        }
+    }
+
+    /// Corrects the co2 value of the measurement with its temperature and
+    /// pressure, without a valid temperature the measurement is unchanged
+    pub fn apply(&self, measurement: Measurement) -> Measurement {
+        let (co2, temperature) = match (measurement.co2(), measurement.temperature()) {
+            (Some(co2), Some(temperature)) => (co2 as f32, temperature),
+            _ => return measurement,
+        };
+        let compensated = match self {
+            Compensation::None => return measurement,
+            Compensation::IdealGas => {
+                let pressure = measurement
+                    .pressure()
+                    .filter(|pressure| *pressure > 0.0)
+                    .unwrap_or(REFERENCE_PRESSURE_HPA);
+                co2 * (temperature + ZERO_CELSIUS_K) / (REFERENCE_TEMPERATURE_C + ZERO_CELSIUS_K)
+                    * REFERENCE_PRESSURE_HPA
+                    / pressure
+            }
+            Compensation::Linear(ppm_per_degree) => {
+                co2 + ppm_per_degree * (temperature - REFERENCE_TEMPERATURE_C)
+            }
+        };
+        let mut compensated_measurement = measurement;
+        compensated_measurement.set_co2(compensated.round() as i32);
+        compensated_measurement
+    }
+}
@@ -0,0 +1,716 @@
+use crate::boot_report::ConfigChecksum;
+use crate::tls;
+use crate::wifi::{IpSettings, Network, WifiAuth};
+use anyhow::{bail, Result};
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys::EspError;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+const NAMESPACE: &str = "co2-sensor";
+const DEFAULT_MEASUREMENT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// gpios of the serial console
+const RESERVED_PINS: [i32; 2] = [1, 3];
+/// Longest string the nvs stores, including the terminating zero
+const MAX_CA_CERT_LEN: usize = 4000;
+
+// settings compiled in from cfg.toml, `CONFIG` holds their values
+#[toml_cfg::toml_config]
+pub struct Config {
+    #[default("")]
+    wifi_ssid: &'static str,
+    #[default("")]
+    wifi_psk: &'static str,
+    #[default("")]
+    wifi_ssid_2: &'static str,
+    #[default("")]
+    wifi_psk_2: &'static str,
+    #[default("")]
+    wifi_ssid_3: &'static str,
+    #[default("")]
+    wifi_psk_3: &'static str,
+    #[default("wpa2")]
+    wifi_auth: &'static str,
+    #[default("")]
+    wifi_identity: &'static str,
+    #[default("")]
+    wifi_user: &'static str,
+    #[default(false)]
+    wifi_scan: bool,
+    #[default("localhost")]
+    mqtt_host: &'static str,
+    #[default("")]
+    mqtt_user: &'static str,
+    #[default("")]
+    mqtt_pass: &'static str,
+    #[default(false)]
+    mqtt_tls: bool,
+    #[default(false)]
+    mqtt_commands: bool,
+    #[default("")]
+    ca_cert: &'static str,
+    #[default("")]
+    static_ip: &'static str,
+    #[default("")]
+    gateway: &'static str,
+    #[default("")]
+    dns: &'static str,
+    #[default("")]
+    hostname: &'static str,
+    #[default("esp-bedroom")]
+    location: &'static str,
+    #[default(false)]
+    hub_mode: bool,
+    #[default("")]
+    hub_host: &'static str,
+    #[default("UTC0")]
+    timezone: &'static str,
+    #[default(7)]
+    calibration_weekday: u8,
+    #[default(3)]
+    calibration_start_hour: u8,
+    #[default(4)]
+    calibration_end_hour: u8,
+    #[default(6)]
+    calibration_unoccupied_hours: u64,
+    #[default(30)]
+    calibration_max_spread: i32,
+    #[default(0)]
+    max_sample_age_ms: u64,
+    #[default(false)]
+    mhz19_secondary: bool,
+    #[default(100)]
+    drift_threshold_ppm: i32,
+    #[default(60)]
+    drift_period_min: u64,
+    #[default(true)]
+    align_samples: bool,
+    #[default(false)]
+    privacy_mode: bool,
+    #[default(0)]
+    mhz19_range: u16,
+    #[default(false)]
+    mhz19_trace: bool,
+    #[default(false)]
+    dht11: bool,
+    #[default(true)]
+    dht_critical_section: bool,
+    #[default(false)]
+    dht_rmt: bool,
+    #[default(false)]
+    dht_isr: bool,
+    #[default(4)]
+    dht_pin: i32,
+    #[default(33)]
+    mhz19_tx_pin: i32,
+    #[default(32)]
+    mhz19_rx_pin: i32,
+    #[default(2)]
+    led_pin: i32,
+    #[default(21)]
+    i2c_sda_pin: i32,
+    #[default(22)]
+    i2c_scl_pin: i32,
+    #[default(25)]
+    ws2812_pin: i32,
+    #[default(17)]
+    mhz19_secondary_tx_pin: i32,
+    #[default(16)]
+    mhz19_secondary_rx_pin: i32,
+    #[default("mhz19")]
+    co2_sensor: &'static str,
+    #[default("dht22")]
+    climate_sensor: &'static str,
+    #[default(0x76)]
+    bme280_address: u8,
+    #[default(0x44)]
+    sht_address: u8,
+    #[default("")]
+    extra_dht: &'static str,
+    #[default("high")]
+    sht_precision: &'static str,
+    #[default(false)]
+    ds3231: bool,
+    #[default(false)]
+    ina219: bool,
+    #[default(100)]
+    ina219_shunt_milliohm: u32,
+    #[default("")]
+    outdoor_latitude: &'static str,
+    #[default("")]
+    outdoor_longitude: &'static str,
+    #[default(60)]
+    outdoor_interval_min: u64,
+    #[default(true)]
+    homeassistant_discovery: bool,
+    #[default(false)]
+    legacy_topics: bool,
+    #[default("none")]
+    filter: &'static str,
+    #[default(5)]
+    filter_window: usize,
+    #[default(0.3)]
+    filter_alpha: f32,
+    #[default("none")]
+    co2_compensation: &'static str,
+    #[default(0.0)]
+    co2_compensation_ppm_per_c: f32,
+    #[default(288)]
+    history_len: usize,
+    #[default(0)]
+    spool_len: usize,
+    #[default("")]
+    influx_url: &'static str,
+    #[default("")]
+    influx_org: &'static str,
+    #[default("")]
+    influx_bucket: &'static str,
+    #[default("")]
+    influx_token: &'static str,
+    #[default(10)]
+    influx_batch: usize,
+    #[default("log,mqtt,influx,display")]
+    outputs: &'static str,
+    #[default("")]
+    output_intervals: &'static str,
+    #[default(0)]
+    co2_interval_s: u64,
+    #[default(0)]
+    climate_interval_s: u64,
+    #[default(60)]
+    stats_interval_min: u64,
+    #[default(5)]
+    fault_threshold: u32,
+    #[default(10)]
+    fault_probe_min: u64,
+    #[default(true)]
+    status_server: bool,
+    #[default(true)]
+    mdns: bool,
+    #[default(1400)]
+    co2_alert_ppm: i32,
+    #[default(false)]
+    ws2812: bool,
+    #[default(1000)]
+    co2_warn_ppm: i32,
+    #[default(32)]
+    ws2812_brightness: u8,
+    #[default("none")]
+    display: &'static str,
+}
+
+/// Gpio numbers of the sensors and the status led
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinMapping {
+    pub dht: i32,
+    pub mhz19_tx: i32,
+    pub mhz19_rx: i32,
+    pub led: i32,
+    /// the pins of the i2c bus, the WS2812 and the second co2 sensor are only
+    /// set in cfg.toml, they are kept free even if the peripheral is unused
+    pub i2c_sda: i32,
+    pub i2c_scl: i32,
+    pub ws2812: i32,
+    pub mhz19_secondary_tx: i32,
+    pub mhz19_secondary_rx: i32,
+}
+
+impl PinMapping {
+    /// Returns the pins compiled in from cfg.toml
+    fn defaults(config: &Config) -> Self {
+        Self {
+            dht: config.dht_pin,
+            mhz19_tx: config.mhz19_tx_pin,
+            mhz19_rx: config.mhz19_rx_pin,
+            led: config.led_pin,
+            i2c_sda: config.i2c_sda_pin,
+            i2c_scl: config.i2c_scl_pin,
+            ws2812: config.ws2812_pin,
+            mhz19_secondary_tx: config.mhz19_secondary_tx_pin,
+            mhz19_secondary_rx: config.mhz19_secondary_rx_pin,
+        }
+    }
+
+    fn pins(&self) -> [i32; 9] {
+        [
+            self.dht,
+            self.mhz19_tx,
+            self.mhz19_rx,
+            self.led,
+            self.i2c_sda,
+            self.i2c_scl,
+            self.ws2812,
+            self.mhz19_secondary_tx,
+            self.mhz19_secondary_rx,
+        ]
+    }
+
+    /// The pins must be usable gpios and distinct
+    pub fn is_valid(&self) -> bool {
+        let pins = self.pins();
+        pins.iter()
+            .enumerate()
+            .all(|(i, pin)| is_usable(*pin) && !pins[i + 1..].contains(pin))
+    }
+
+    /// Returns true if the pin is a usable gpio not used by the mapping or
+    /// other peripherals, e.g. for an additional sensor
+    pub fn is_free(&self, pin: i32) -> bool {
+        is_usable(pin) && !self.pins().contains(&pin)
+    }
+}
+
+/// Gpios that are neither input only, nor connected to the flash, nor reserved
+fn is_usable(pin: i32) -> bool {
+    matches!(pin, 0..=5 | 12..=19 | 21..=23 | 25..=27 | 32 | 33) && !RESERVED_PINS.contains(&pin)
+}
+
+/// Settings that can be changed without recompiling, stored values in the
+/// nvs override the ones compiled in from cfg.toml
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppConfig {
+    pub wifi_ssid: String,
+    pub wifi_psk: String,
+    /// fallback networks tried in turn when the first one is not reachable
+    pub wifi_ssid_2: String,
+    pub wifi_psk_2: String,
+    pub wifi_ssid_3: String,
+    pub wifi_psk_3: String,
+    /// `open`, `wpa2`, `wpa3` or `enterprise`
+    pub wifi_auth: String,
+    /// outer identity and user name of WPA2-Enterprise networks
+    pub wifi_identity: String,
+    pub wifi_user: String,
+    pub mqtt_host: String,
+    pub mqtt_user: String,
+    pub mqtt_pass: String,
+    /// PEM encoded CA certificate the servers of TLS connections are verified
+    /// with, empty for the certificate bundle of esp-idf
+    pub ca_cert: String,
+    pub measurement_interval: Duration,
+    pub pins: PinMapping,
+    /// fixed address with prefix length, e.g. `192.168.1.50/24`, empty for DHCP
+    pub static_ip: String,
+    pub gateway: String,
+    pub dns: String,
+    /// name registered with the DHCP server, empty for the default
+    pub hostname: String,
+}
+
+impl AppConfig {
+    /// Returns the values compiled in from cfg.toml
+    pub fn defaults(config: &Config) -> Self {
+        Self {
+            wifi_ssid: config.wifi_ssid.into(),
+            wifi_psk: config.wifi_psk.into(),
+            wifi_ssid_2: config.wifi_ssid_2.into(),
+            wifi_psk_2: config.wifi_psk_2.into(),
+            wifi_ssid_3: config.wifi_ssid_3.into(),
+            wifi_psk_3: config.wifi_psk_3.into(),
+            wifi_auth: config.wifi_auth.into(),
+            wifi_identity: config.wifi_identity.into(),
+            wifi_user: config.wifi_user.into(),
+            mqtt_host: config.mqtt_host.into(),
+            mqtt_user: config.mqtt_user.into(),
+            mqtt_pass: config.mqtt_pass.into(),
+            ca_cert: config.ca_cert.into(),
+            measurement_interval: DEFAULT_MEASUREMENT_INTERVAL,
+            pins: PinMapping::defaults(config),
+            static_ip: config.static_ip.into(),
+            gateway: config.gateway.into(),
+            dns: config.dns.into(),
+            hostname: config.hostname.into(),
+        }
+    }
+
+    /// Returns the configured networks in the order they are tried
+    pub fn wifi_networks(&self) -> Vec<Network> {
+        [
+            (&self.wifi_ssid, &self.wifi_psk),
+            (&self.wifi_ssid_2, &self.wifi_psk_2),
+            (&self.wifi_ssid_3, &self.wifi_psk_3),
+        ]
+        .into_iter()
+        .filter(|(ssid, _)| !ssid.is_empty())
+        .map(|(ssid, pass)| Network {
+            ssid: ssid.clone(),
+            pass: pass.clone(),
+        })
+        .collect()
+    }
+
+    /// Returns the authentication of the station, an unknown method falls back
+    /// to WPA2 with password
+    pub fn wifi_auth(&self) -> WifiAuth {
+        WifiAuth::from_setting(&self.wifi_auth, &self.wifi_identity, &self.wifi_user)
+            .unwrap_or_else(|| {
+                log::warn!("Unknown wifi authentication {}, using wpa2", self.wifi_auth);
+                WifiAuth::Wpa2Personal
+            })
+    }
+
+    /// Returns the addresses of the station, an invalid static configuration
+    /// falls back to DHCP
+    pub fn ip_settings(&self) -> IpSettings {
+        let hostname = (!self.hostname.is_empty()).then(|| self.hostname.clone());
+        if self.static_ip.is_empty() {
+            return IpSettings {
+                hostname,
+                ..Default::default()
+            };
+        }
+        match (parse_cidr(&self.static_ip), self.gateway.parse()) {
+            (Some(static_ip), Ok(gateway)) => IpSettings {
+                static_ip: Some(static_ip),
+                gateway: Some(gateway),
+                dns: self.dns.parse().ok(),
+                hostname,
+            },
+            _ => {
+                log::warn!(
+                    "Invalid static ip {} or gateway {}, using DHCP",
+                    self.static_ip,
+                    self.gateway
+                );
+                IpSettings {
+                    hostname,
+                    ..Default::default()
+                }
+            }
+        }
+    }
+}
+
+/// Parses an address with prefix length, e.g. `192.168.1.50/24`
+fn parse_cidr(value: &str) -> Option<(Ipv4Addr, u8)> {
+    let (ip, prefix) = value.split_once('/')?;
+    let prefix = prefix.parse().ok().filter(|prefix| *prefix <= 32)?;
+    Some((ip.parse().ok()?, prefix))
+}
+
+/// Host names only allow letters, digits and `-`, DHCP limits them to 30 characters
+fn is_valid_hostname(value: &str) -> bool {
+    value.len() <= 30
+        && !value.starts_with('-')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// The values that can be stored, the names are used as nvs keys and on the console
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    WifiSsid,
+    WifiPsk,
+    MqttHost,
+    MqttUser,
+    MqttPass,
+    Interval,
+    DhtPin,
+    MHz19TxPin,
+    MHz19RxPin,
+    LedPin,
+    StaticIp,
+    Gateway,
+    Dns,
+    Hostname,
+    WifiAuth,
+    WifiIdentity,
+    WifiUser,
+    WifiSsid2,
+    WifiPsk2,
+    WifiSsid3,
+    WifiPsk3,
+    CaCert,
+}
+
+impl Key {
+    const ALL: [Key; 22] = [
+        Key::WifiSsid,
+        Key::WifiPsk,
+        Key::MqttHost,
+        Key::MqttUser,
+        Key::MqttPass,
+        Key::Interval,
+        Key::DhtPin,
+        Key::MHz19TxPin,
+        Key::MHz19RxPin,
+        Key::LedPin,
+        Key::StaticIp,
+        Key::Gateway,
+        Key::Dns,
+        Key::Hostname,
+        Key::WifiAuth,
+        Key::WifiIdentity,
+        Key::WifiUser,
+        Key::WifiSsid2,
+        Key::WifiPsk2,
+        Key::WifiSsid3,
+        Key::WifiPsk3,
+        Key::CaCert,
+    ];
+
+    /// Nvs keys are limited to 15 characters
+    pub fn name(self) -> &'static str {
+        match self {
+            Key::WifiSsid => "wifi_ssid",
+            Key::WifiPsk => "wifi_psk",
+            Key::MqttHost => "mqtt_host",
+            Key::MqttUser => "mqtt_user",
+            Key::MqttPass => "mqtt_pass",
+            Key::Interval => "interval_s",
+            Key::DhtPin => "dht_pin",
+            Key::MHz19TxPin => "mhz19_tx_pin",
+            Key::MHz19RxPin => "mhz19_rx_pin",
+            Key::LedPin => "led_pin",
+            Key::StaticIp => "static_ip",
+            Key::Gateway => "gateway",
+            Key::Dns => "dns",
+            Key::Hostname => "hostname",
+            Key::WifiAuth => "wifi_auth",
+            Key::WifiIdentity => "wifi_identity",
+            Key::WifiUser => "wifi_user",
+            Key::WifiSsid2 => "wifi_ssid_2",
+            Key::WifiPsk2 => "wifi_psk_2",
+            Key::WifiSsid3 => "wifi_ssid_3",
+            Key::WifiPsk3 => "wifi_psk_3",
+            Key::CaCert => "ca_cert",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|key| key.name() == name)
+    }
+}
+
+/// Loads and stores the configuration in the default nvs partition
+pub struct ConfigStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl ConfigStore {
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<Self, EspError> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, NAMESPACE, true)?,
+        })
+    }
+
+    /// Returns the defaults overridden with the stored values, unreadable
+    /// values and invalid pin mappings fall back to the defaults
+    pub fn load(&self, defaults: AppConfig) -> AppConfig {
+        let mut config = defaults;
+        self.load_str(Key::WifiSsid, &mut config.wifi_ssid);
+        self.load_str(Key::WifiPsk, &mut config.wifi_psk);
+        self.load_str(Key::WifiAuth, &mut config.wifi_auth);
+        self.load_str(Key::WifiIdentity, &mut config.wifi_identity);
+        self.load_str(Key::WifiUser, &mut config.wifi_user);
+        self.load_str(Key::WifiSsid2, &mut config.wifi_ssid_2);
+        self.load_str(Key::WifiPsk2, &mut config.wifi_psk_2);
+        self.load_str(Key::WifiSsid3, &mut config.wifi_ssid_3);
+        self.load_str(Key::WifiPsk3, &mut config.wifi_psk_3);
+        self.load_str(Key::MqttHost, &mut config.mqtt_host);
+        self.load_str(Key::MqttUser, &mut config.mqtt_user);
+        self.load_str(Key::MqttPass, &mut config.mqtt_pass);
+        self.load_str(Key::StaticIp, &mut config.static_ip);
+        self.load_str(Key::Gateway, &mut config.gateway);
+        self.load_str(Key::Dns, &mut config.dns);
+        self.load_str(Key::Hostname, &mut config.hostname);
+        self.load_str(Key::CaCert, &mut config.ca_cert);
+        if let Some(seconds) = self.load_u32(Key::Interval) {
+            config.measurement_interval = Duration::from_secs(seconds as u64);
+        }
+
+        let mut pins = config.pins;
+        for (key, pin) in [
+            (Key::DhtPin, &mut pins.dht),
+            (Key::MHz19TxPin, &mut pins.mhz19_tx),
+            (Key::MHz19RxPin, &mut pins.mhz19_rx),
+            (Key::LedPin, &mut pins.led),
+        ] {
+            if let Some(stored) = self.load_u32(key) {
+                *pin = stored as i32;
+            }
+        }
+        if pins.is_valid() {
+            config.pins = pins;
+        } else {
+            log::warn!("Invalid pin mapping {:?}, using the default pins", pins);
+        }
+        config
+    }
+
+    fn load_str(&self, key: Key, value: &mut String) {
+        let mut buf = vec![
+            0u8;
+            if key == Key::CaCert {
+                MAX_CA_CERT_LEN
+            } else {
+                128
+            }
+        ];
+        match self.nvs.get_str(key.name(), &mut buf) {
+            Ok(Some(stored)) => *value = stored.into(),
+            Ok(None) => {}
+            Err(err) => log::warn!("error loading {}: {:}", key.name(), err),
+        }
+    }
+
+    fn load_u32(&self, key: Key) -> Option<u32> {
+        self.nvs
+            .get_u32(key.name())
+            .map_err(|err| log::warn!("error loading {}: {:}", key.name(), err))
+            .ok()
+            .flatten()
+    }
+
+    /// Stores a single value given as text, it is used after the next restart
+    pub fn set(&mut self, key: Key, value: &str) -> Result<()> {
+        match key {
+            Key::WifiSsid
+            | Key::WifiPsk
+            | Key::WifiIdentity
+            | Key::WifiUser
+            | Key::WifiSsid2
+            | Key::WifiPsk2
+            | Key::WifiSsid3
+            | Key::WifiPsk3
+            | Key::MqttHost
+            | Key::MqttUser
+            | Key::MqttPass => {
+                self.nvs.set_str(key.name(), value)?;
+            }
+            // empty values switch back to DHCP and the default host name
+            Key::StaticIp if value.is_empty() || parse_cidr(value).is_some() => {
+                self.nvs.set_str(key.name(), value)?;
+            }
+            Key::Gateway | Key::Dns if value.is_empty() || value.parse::<Ipv4Addr>().is_ok() => {
+                self.nvs.set_str(key.name(), value)?;
+            }
+            Key::Hostname if value.is_empty() || is_valid_hostname(value) => {
+                self.nvs.set_str(key.name(), value)?;
+            }
+            // the certificate is pasted on one line, an empty value restores the bundle
+            Key::CaCert if value.is_empty() => self.nvs.set_str(key.name(), value)?,
+            Key::CaCert => match tls::normalize_pem(value) {
+                Some(pem) if pem.len() < MAX_CA_CERT_LEN => self.nvs.set_str(key.name(), &pem)?,
+                _ => bail!("invalid ca_cert, expected a PEM certificate"),
+            },
+            Key::WifiAuth if WifiAuth::from_setting(value, "", "").is_some() => {
+                self.nvs.set_str(key.name(), value)?;
+            }
+            Key::StaticIp | Key::Gateway | Key::Dns | Key::Hostname | Key::WifiAuth => {
+                bail!("invalid {}: {}", key.name(), value)
+            }
+            Key::Interval => match value.parse::<u32>() {
+                Ok(seconds) if seconds > 0 => self.nvs.set_u32(key.name(), seconds)?,
+                _ => bail!("invalid interval: {}", value),
+            },
+            Key::DhtPin | Key::MHz19TxPin | Key::MHz19RxPin | Key::LedPin => {
+                match value.parse::<u32>() {
+                    // the mapping as a whole is checked when it is loaded
+                    Ok(pin) if pin <= 39 => self.nvs.set_u32(key.name(), pin)?,
+                    _ => bail!("invalid pin: {}", value),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes all stored values, the defaults are used after the next restart
+    pub fn reset(&mut self) -> Result<(), EspError> {
+        for key in Key::ALL {
+            self.nvs.remove(key.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// Checksum over the configuration values, secrets are left out
+pub fn checksum(config: &Config, settings: &AppConfig) -> u32 {
+    let mut checksum = ConfigChecksum::default();
+    checksum
+        .add(&settings.wifi_ssid)
+        .add(&settings.wifi_ssid_2)
+        .add(&settings.wifi_ssid_3)
+        .add(&settings.wifi_auth)
+        .add(&settings.wifi_identity)
+        .add(&settings.wifi_user)
+        .add(&settings.mqtt_host)
+        .add(&settings.mqtt_user)
+        .add(&settings.measurement_interval.as_secs())
+        .add(&settings.pins.dht)
+        .add(&settings.pins.mhz19_tx)
+        .add(&settings.pins.mhz19_rx)
+        .add(&settings.pins.led)
+        .add(&settings.pins.i2c_sda)
+        .add(&settings.pins.i2c_scl)
+        .add(&settings.pins.ws2812)
+        .add(&settings.pins.mhz19_secondary_tx)
+        .add(&settings.pins.mhz19_secondary_rx)
+        .add(&settings.static_ip)
+        .add(&settings.gateway)
+        .add(&settings.dns)
+        .add(&settings.hostname)
+        .add(&config.location)
+        .add(&config.hub_mode)
+        .add(&config.hub_host)
+        .add(&config.timezone)
+        .add(&config.calibration_weekday)
+        .add(&config.calibration_start_hour)
+        .add(&config.calibration_end_hour)
+        .add(&config.calibration_unoccupied_hours)
+        .add(&config.calibration_max_spread)
+        .add(&config.max_sample_age_ms)
+        .add(&config.mhz19_secondary)
+        .add(&config.drift_threshold_ppm)
+        .add(&config.drift_period_min)
+        .add(&config.align_samples)
+        .add(&config.privacy_mode)
+        .add(&config.mhz19_range)
+        .add(&config.mhz19_trace)
+        .add(&config.dht11)
+        .add(&config.dht_critical_section)
+        .add(&config.ds3231)
+        .add(&config.ina219)
+        .add(&config.ina219_shunt_milliohm)
+        .add(&config.outdoor_latitude)
+        .add(&config.outdoor_longitude)
+        .add(&config.outdoor_interval_min)
+        .add(&config.homeassistant_discovery)
+        .add(&config.legacy_topics)
+        .add(&config.filter)
+        .add(&config.filter_window)
+        .add(&config.filter_alpha)
+        .add(&config.status_server)
+        .add(&config.mdns)
+        .add(&config.mqtt_tls)
+        .add(&config.mqtt_commands)
+        .add(&config.wifi_scan)
+        .add(&config.co2_alert_ppm)
+        .add(&config.ws2812)
+        .add(&config.co2_warn_ppm)
+        .add(&config.ws2812_brightness)
+        .add(&config.display)
+        .add(&config.dht_rmt)
+        .add(&config.dht_isr)
+        .add(&config.co2_sensor)
+        .add(&config.climate_sensor)
+        .add(&config.bme280_address)
+        .add(&config.sht_address)
+        .add(&config.sht_precision)
+        .add(&config.extra_dht)
+        .add(&config.co2_compensation)
+        .add(&config.co2_compensation_ppm_per_c)
+        .add(&config.history_len)
+        .add(&config.spool_len)
+        .add(&config.influx_url)
+        .add(&config.influx_org)
+        .add(&config.influx_bucket)
+        .add(&config.influx_batch)
+        .add(&config.outputs)
+        .add(&config.output_intervals)
+        .add(&config.co2_interval_s)
+        .add(&config.climate_interval_s)
+        .add(&config.stats_interval_min)
+        .add(&config.fault_threshold)
+        .add(&config.fault_probe_min);
+    checksum.value()
+}
@@ -0,0 +1,151 @@
+use crate::config::Key;
+#[cfg(debug_assertions)]
+use crate::faults::Fault;
+use anyhow::Result;
+use std::io::{self, BufRead};
+use std::sync::mpsc::Sender;
+use std::{thread, thread::sleep, time::Duration};
+
+/// Commands accepted on the serial console
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// probe all peripherals and report the results
+    SelfTest,
+    /// stop or resume publishing, readings are still logged on the console
+    Privacy(bool),
+    /// store a configuration value in the nvs
+    Config(Key, String),
+    /// remove the stored configuration values
+    ConfigReset,
+    /// send a raw frame to the MH-Z19, the checksum byte is recalculated
+    MHz19Raw([u8; 9]),
+    /// calibrate the zero point of the MH-Z19 to 400 ppm
+    MHz19Zero,
+    /// calibrate the span of the MH-Z19 to the given concentration
+    MHz19Span(u16),
+    /// log every frame exchanged with the MH-Z19
+    MHz19Trace(bool),
+    /// enable or disable the automatic baseline correction of the MH-Z19
+    MHz19Abc(bool),
+    /// take the next measurement right away
+    Measure,
+    /// change the measurement interval in seconds, the interval is stored
+    Interval(u32),
+    /// change the co2 warning and alert thresholds in ppm until the next restart
+    Thresholds(i32, i32),
+    /// restart the node
+    Reboot,
+    /// read the sensors at maximum rate and report statistics until the next reboot
+    Soak,
+    /// inject a fault, only available in debug builds
+    #[cfg(debug_assertions)]
+    Fault(Fault),
+}
+
+impl Command {
+    /// Parses a command received on the MQTT command topic, only the commands
+    /// that leave the configuration and the tests alone are accepted
+    pub fn parse_remote(payload: &str) -> Option<Self> {
+        Self::parse(payload).filter(|command| {
+            matches!(
+                command,
+                Command::Measure
+                    | Command::MHz19Abc(_)
+                    | Command::MHz19Zero
+                    | Command::Interval(_)
+                    | Command::Thresholds(..)
+                    | Command::Reboot
+            )
+        })
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        match (words.next()?, words.next()) {
+            ("selftest", None) => Some(Command::SelfTest),
+            ("soak", None) => Some(Command::Soak),
+            ("measure", None) => Some(Command::Measure),
+            ("reboot", None) => Some(Command::Reboot),
+            ("interval", Some(seconds)) => match seconds.parse() {
+                Ok(seconds) if seconds > 0 => Some(Command::Interval(seconds)),
+                _ => None,
+            },
+            ("thresholds", Some(warn)) => {
+                let warn = warn.parse().ok()?;
+                let alert = words.next()?.parse().ok()?;
+                (0 < warn && warn <= alert).then_some(Command::Thresholds(warn, alert))
+            }
+            ("privacy", Some("on")) => Some(Command::Privacy(true)),
+            ("privacy", Some("off")) => Some(Command::Privacy(false)),
+            ("config", Some("reset")) => Some(Command::ConfigReset),
+            ("config", Some(key)) => {
+                // the value may contain spaces, e.g. a wifi name
+                let value = words.collect::<Vec<_>>().join(" ");
+                Some(Command::Config(Key::parse(key)?, value))
+            }
+            ("mhz19", Some("raw")) => {
+                // the checksum byte may be omitted
+                let mut frame = [0u8; 9];
+                let mut len = 0;
+                for word in words {
+                    *frame.get_mut(len)? = u8::from_str_radix(word, 16).ok()?;
+                    len += 1;
+                }
+                (len >= 8).then_some(Command::MHz19Raw(frame))
+            }
+            ("mhz19", Some("zero")) => Some(Command::MHz19Zero),
+            ("mhz19", Some("span")) => Some(Command::MHz19Span(words.next()?.parse().ok()?)),
+            ("mhz19", Some("trace")) => match words.next()? {
+                "on" => Some(Command::MHz19Trace(true)),
+                "off" => Some(Command::MHz19Trace(false)),
+                _ => None,
+            },
+            ("mhz19", Some("abc")) => match words.next()? {
+                "on" => Some(Command::MHz19Abc(true)),
+                "off" => Some(Command::MHz19Abc(false)),
+                _ => None,
+            },
+            #[cfg(debug_assertions)]
+            ("fault", Some(fault)) => {
+                let fault = match fault {
+                    "dht-checksum" => Fault::DhtChecksum,
+                    "mhz19-drop" => Fault::MHz19Drop,
+                    "wifi-drop" => Fault::WifiDrop,
+                    "heap" => Fault::FillHeap(words.next()?.parse().ok()?),
+                    _ => return None,
+                };
+                Some(Command::Fault(fault))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Spawns a thread reading commands line by line from the serial console
+pub fn spawn(commands: Sender<Command>) -> Result<()> {
+    thread::Builder::new()
+        .name("console".into())
+        .stack_size(4096)
+        .spawn(move || {
+            let stdin = io::stdin();
+            let mut line = String::new();
+            loop {
+                // stdin is non-blocking on the esp, partial lines stay in the buffer
+                match stdin.lock().read_line(&mut line) {
+                    Ok(_) if line.ends_with('\n') => {
+                        match Command::parse(&line) {
+                            Some(command) => {
+                                if commands.send(command).is_err() {
+                                    return;
+                                }
+                            }
+                            None => log::warn!("unknown command: {}", line.trim()),
+                        }
+                        line.clear();
+                    }
+                    _ => sleep(Duration::from_millis(100)),
+                }
+            }
+        })?;
+    Ok(())
+}
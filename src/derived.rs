@@ -0,0 +1,47 @@
+/// Coefficients of the Magnus formula over water
+const MAGNUS_B: f32 = 17.62;
+const MAGNUS_C: f32 = 243.12;
+
+/// Saturation vapour pressure in hPa after the Magnus formula
+fn saturation_vapour_pressure(temperature: f32) -> f32 {
+    6.112 * (MAGNUS_B * temperature / (MAGNUS_C + temperature)).exp()
+}
+
+/// Dew point in °C after the Magnus formula
+pub fn dew_point(temperature: f32, humidity: f32) -> f32 {
+    let gamma = (humidity / 100.0).ln() + MAGNUS_B * temperature / (MAGNUS_C + temperature);
+    MAGNUS_C * gamma / (MAGNUS_B - gamma)
+}
+
+/// Water content of the air in g/m³
+pub fn absolute_humidity(temperature: f32, humidity: f32) -> f32 {
+    // vapour pressure over the specific gas constant of water vapour
+    216.7 * humidity / 100.0 * saturation_vapour_pressure(temperature) / (273.15 + temperature)
+}
+
+/// Apparent temperature in °C after the heat index of the US National Weather
+/// Service, below 27 °C it stays close to the air temperature
+pub fn heat_index(temperature: f32, humidity: f32) -> f32 {
+    // the regression is defined in °F
+    let t = temperature * 9.0 / 5.0 + 32.0;
+    let rh = humidity;
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let index = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let mut index = -42.379 + 2.049_015_2 * t + 10.143_331 * rh
+            - 0.224_755_4 * t * rh
+            - 0.006_837_83 * t * t
+            - 0.054_817_17 * rh * rh
+            + 0.001_228_74 * t * t * rh
+            + 0.000_852_82 * t * rh * rh
+            - 0.000_001_99 * t * t * rh * rh;
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            index -= (13.0 - rh) / 4.0 * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            index += (rh - 85.0) / 10.0 * (87.0 - t) / 5.0;
+        }
+        index
+    };
+    (index - 32.0) * 5.0 / 9.0
+}
@@ -1,7 +1,13 @@
+use crate::sensors::{Retrying, TempHum, TempHumSensor};
 use core::fmt;
 use embedded_hal::delay::DelayUs;
 use embedded_hal::digital::{InputPin, OutputPin, PinState};
 
+/// Minimum time between two reads, the sensor does not answer earlier
+pub const MIN_READ_INTERVAL_MS: u32 = 2000;
+/// A logical '1' takes more than 30 polls of the pin to go low again
+const POLLED_ONE_THRESHOLD: u32 = 30;
+
 /// DHT readout data
 #[derive(Debug, Clone, Copy)]
 pub struct ReadoutData {
@@ -32,6 +38,8 @@ pub enum DhtError<HalError> {
     PinError(HalError),
     // checksum error in received data
     CheckSum(u8, u8),
+    // read within the minimum read interval after a failed read
+    TooSoon,
 }
 
 impl<HalError> From<HalError> for DhtError<HalError> {
@@ -48,6 +56,11 @@ impl<HE: fmt::Debug> fmt::Display for DhtError<HE> {
             ReadTimeout => write!(f, "timeout while reading"),
             PinError(err) => write!(f, "HAL pin error: {:?}", err),
             CheckSum(exp, act) => write!(f, "Checksum error: {:x} vs {:x}", exp, act),
+            TooSoon => write!(
+                f,
+                "read within {} ms of the last read",
+                MIN_READ_INTERVAL_MS
+            ),
         }
     }
 }
@@ -55,31 +68,105 @@ impl<HE: fmt::Debug> fmt::Display for DhtError<HE> {
 #[cfg(feature = "std")]
 impl<HE: fmt::Debug> std::error::Error for DhtError<HE> {}
 
-/// A Dht22 sensor
-pub struct Dht22<HalError, D: DelayUs, P: InputPin<Error = HalError> + OutputPin<Error = HalError>>
-{
+/// The sensors of the DHT family share the protocol, only the data bytes
+/// are interpreted differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhtVariant {
+    Dht11,
+    Dht22,
+}
+
+/// A sensor of the DHT family
+pub struct DhtSensor<
+    HalError,
+    D: DelayUs,
+    P: InputPin<Error = HalError> + OutputPin<Error = HalError>,
+> {
     delay: D,
     pin: P,
+    variant: DhtVariant,
+    critical_section: Option<fn(&mut dyn FnMut())>,
+    clock: Option<fn() -> u64>,
+    /// time in milliseconds and value of the last read, none if it failed
+    last_read: Option<(u64, Option<ReadoutData>)>,
 }
 
-impl<HE, D: DelayUs, P: InputPin<Error = HE> + OutputPin<Error = HE>> Dht22<HE, D, P> {
+/// A Dht22 sensor
+pub type Dht22<HalError, D, P> = DhtSensor<HalError, D, P>;
+
+impl<HE, D: DelayUs, P: InputPin<Error = HE> + OutputPin<Error = HE>> DhtSensor<HE, D, P> {
+    /// Creates the driver for a DHT22
     pub fn new(delay: D, pin: P) -> Self {
-        Self { delay, pin }
+        Self::with_variant(delay, pin, DhtVariant::Dht22)
     }
 
-    fn parse_buffer(buf: &[u8]) -> (f32, f32) {
-        let humidity = (((buf[0] as u16) << 8) + buf[1] as u16) as f32 / 10.0;
-        let mut temp = ((((buf[2] & 0x7f) as u16) << 8) | buf[3] as u16) as f32 / 10.0;
-        if buf[2] & 0x80 != 0 {
-            temp = -temp;
+    pub fn with_variant(delay: D, pin: P, variant: DhtVariant) -> Self {
+        Self {
+            delay,
+            pin,
+            variant,
+            critical_section: None,
+            clock: None,
+            last_read: None,
         }
-        (humidity, temp)
+    }
+
+    /// Runs the timing sensitive part of every read within the given critical
+    /// section, e.g. with interrupts disabled so the scheduler cannot preempt it
+    pub fn set_critical_section(&mut self, critical_section: fn(&mut dyn FnMut())) {
+        self.critical_section = Some(critical_section);
+    }
+
+    /// Limits the reads to one per `MIN_READ_INTERVAL_MS` with the given clock
+    /// in milliseconds, a read within the interval returns the value of the
+    /// last read or `DhtError::TooSoon` if it failed
+    pub fn set_clock(&mut self, clock: fn() -> u64) {
+        self.clock = Some(clock);
     }
 
     pub fn read(&mut self) -> Result<ReadoutData, DhtError<HE>> {
-        // wake up dht22
+        let now = self.clock.map(|clock| clock());
+        if let (Some(now), Some((last_time, last_value))) = (now, self.last_read) {
+            if now.saturating_sub(last_time) < MIN_READ_INTERVAL_MS as u64 {
+                return last_value.ok_or(DhtError::TooSoon);
+            }
+        }
+        let result = self.read_sensor();
+        if let Some(now) = now {
+            self.last_read = Some((now, result.as_ref().ok().copied()));
+        }
+        result
+    }
+
+    /// Reads the sensor up to the given number of attempts, the retries are
+    /// spaced by the backoff but at least by the minimum read interval. Returns
+    /// the error of the last attempt if all of them fail.
+    pub fn read_with_retry(
+        &mut self,
+        attempts: u32,
+        backoff_ms: u32,
+    ) -> Result<ReadoutData, DhtError<HE>> {
+        Retrying::new(self, attempts, backoff_ms.max(MIN_READ_INTERVAL_MS)).retry(|dht, wait_ms| {
+            dht.delay.delay_ms(wait_ms);
+            dht.read()
+        })
+    }
+
+    fn read_sensor(&mut self) -> Result<ReadoutData, DhtError<HE>> {
+        // wake up the sensor, the dht11 needs at least 18 ms
         self.pin.set_low()?;
         self.delay.delay_us(18000);
+
+        let mut result = Err(DhtError::ReadTimeout);
+        match self.critical_section {
+            Some(critical_section) => critical_section(&mut || result = self.read_bits()),
+            None => result = self.read_bits(),
+        }
+        decode_pulses(self.variant, &result?, POLLED_ONE_THRESHOLD)
+    }
+
+    /// Captures the durations of the high pulses of the 40 data bits
+    fn read_bits(&mut self) -> Result<[u32; 40], DhtError<HE>> {
         // ask for data
         self.pin.set_high()?;
         // self.delay.delay_us(30);
@@ -89,33 +176,15 @@ impl<HE, D: DelayUs, P: InputPin<Error = HE> + OutputPin<Error = HE>> Dht22<HE,
         self.wait_for_state(PinState::High, 80, DhtError::NotFoundOnGPio)?;
         self.wait_for_state(PinState::Low, 80, DhtError::NotFoundOnGPio)?;
 
-        // read the 40 data bits
-        let mut buf: [u8; 5] = [0; 5];
-        for bit in 0..40 {
+        // read the 40 data bits, nothing may be logged until the end
+        let mut durations = [0u32; 40];
+        for duration in durations.iter_mut() {
             // wait for next high state
             self.wait_for_state(PinState::High, 50, DhtError::ReadTimeout)?;
             // check how long it takes to go low again
-            let elapsed = self.wait_for_state(PinState::Low, 70, DhtError::ReadTimeout)?;
-            // a logical '1' will take more than 30us to go low again
-            if elapsed > 30 {
-                let byte = bit / 8;
-                let shift = 7 - bit % 8;
-                buf[byte] |= 1 << shift;
-            }
+            *duration = self.wait_for_state(PinState::Low, 70, DhtError::ReadTimeout)?;
         }
-
-        let checksum = (buf[0..=3]
-            .iter()
-            .fold(0u16, |accum, next| accum + *next as u16)
-            & 0xff) as u8;
-        if checksum == buf[4] {
-            let (humidity, temp) = Self::parse_buffer(&buf);
-            return Ok(ReadoutData {
-                humidity,
-                temperature: temp,
-            });
-        }
-        Err(DhtError::CheckSum(checksum, buf[4]))
+        Ok(durations)
     }
 
     fn wait_for_state(
@@ -138,3 +207,209 @@ impl<HE, D: DelayUs, P: InputPin<Error = HE> + OutputPin<Error = HE>> Dht22<HE,
         Err(timeout_error)
     }
 }
+
+impl<HE, D: DelayUs, P: InputPin<Error = HE> + OutputPin<Error = HE>> TempHumSensor
+    for DhtSensor<HE, D, P>
+{
+    type Error = DhtError<HE>;
+
+    fn read_temp_hum(&mut self, _delay: &mut dyn DelayUs) -> Result<TempHum, DhtError<HE>> {
+        self.read().map(TempHum::from)
+    }
+}
+
+impl From<ReadoutData> for TempHum {
+    fn from(data: ReadoutData) -> Self {
+        TempHum {
+            temperature: data.temperature,
+            humidity: Some(data.humidity),
+            pressure: None,
+        }
+    }
+}
+
+/// Decodes the durations of the high pulses of a transmission, a pulse longer
+/// than `one_threshold` is a logical '1'. Only the last 40 pulses are data
+/// bits, the ones before, e.g. the response of the sensor, are ignored.
+pub fn decode_pulses<HE>(
+    variant: DhtVariant,
+    durations: &[u32],
+    one_threshold: u32,
+) -> Result<ReadoutData, DhtError<HE>> {
+    if durations.len() < 40 {
+        return Err(DhtError::ReadTimeout);
+    }
+    let durations = &durations[durations.len() - 40..];
+    #[cfg(feature = "trace")]
+    log::trace!("DHT22 bit durations {:?}", durations);
+
+    let mut buf = [0u8; 5];
+    for (bit, duration) in durations.iter().enumerate() {
+        if *duration > one_threshold {
+            buf[bit / 8] |= 1 << (7 - bit % 8);
+        }
+    }
+    decode(variant, &buf)
+}
+
+/// Checks the checksum of the 5 received bytes and converts the values
+fn decode<HE>(variant: DhtVariant, buf: &[u8; 5]) -> Result<ReadoutData, DhtError<HE>> {
+    let checksum = (buf[0..=3]
+        .iter()
+        .fold(0u16, |accum, next| accum + *next as u16)
+        & 0xff) as u8;
+    if checksum != buf[4] {
+        return Err(DhtError::CheckSum(checksum, buf[4]));
+    }
+
+    let (humidity, temperature) = match variant {
+        // integral and decimal part in separate bytes, the sign in the msb of the temperature decimal
+        DhtVariant::Dht11 => {
+            let humidity = buf[0] as f32 + buf[1] as f32 / 10.0;
+            let mut temp = buf[2] as f32 + (buf[3] & 0x7f) as f32 / 10.0;
+            if buf[3] & 0x80 != 0 {
+                temp = -temp;
+            }
+            (humidity, temp)
+        }
+        // tenths in 16 bits, the sign in the msb of the temperature
+        DhtVariant::Dht22 => {
+            let humidity = (((buf[0] as u16) << 8) + buf[1] as u16) as f32 / 10.0;
+            let mut temp = ((((buf[2] & 0x7f) as u16) << 8) | buf[3] as u16) as f32 / 10.0;
+            if buf[2] & 0x80 != 0 {
+                temp = -temp;
+            }
+            (humidity, temp)
+        }
+    };
+    Ok(ReadoutData {
+        humidity,
+        temperature,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::pin::{Mock as PinMock, State, Transaction};
+
+    /// High pulse durations of a transmission of the given bytes
+    fn pulses(bytes: [u8; 5]) -> [u32; 40] {
+        let mut durations = [0u32; 40];
+        for (bit, duration) in durations.iter_mut().enumerate() {
+            let one = bytes[bit / 8] & (1 << (7 - bit % 8)) != 0;
+            *duration = if one { 50 } else { 10 };
+        }
+        durations
+    }
+
+    fn decode_bytes(variant: DhtVariant, bytes: [u8; 5]) -> Result<ReadoutData, DhtError<()>> {
+        decode_pulses(variant, &pulses(bytes), POLLED_ONE_THRESHOLD)
+    }
+
+    #[test]
+    fn decodes_the_dht22_datasheet_example() {
+        let data = decode_bytes(DhtVariant::Dht22, [0x02, 0x8C, 0x01, 0x5F, 0xEE]).unwrap();
+        assert_eq!(data.humidity(), 65.2);
+        assert_eq!(data.temperature(), 35.1);
+    }
+
+    #[test]
+    fn decodes_negative_temperatures() {
+        let data = decode_bytes(DhtVariant::Dht22, [0x02, 0x8C, 0x80, 0x65, 0x73]).unwrap();
+        assert_eq!(data.temperature(), -10.1);
+        let data = decode_bytes(DhtVariant::Dht11, [45, 0, 2, 0x83, 0xB2]).unwrap();
+        assert_eq!(data.humidity(), 45.0);
+        assert_eq!(data.temperature(), -2.3);
+    }
+
+    #[test]
+    fn checksum_wraps_around() {
+        let data = decode_bytes(DhtVariant::Dht11, [0xFF, 0xFF, 0xFF, 0x00, 0xFD]);
+        assert!(data.is_ok());
+        assert!(matches!(
+            decode_bytes(DhtVariant::Dht22, [0x02, 0x8C, 0x01, 0x5F, 0xEF]),
+            Err(DhtError::CheckSum(0xEE, 0xEF))
+        ));
+    }
+
+    #[test]
+    fn ignores_the_pulses_before_the_data_bits() {
+        let mut durations = [80u32; 42];
+        durations[2..].copy_from_slice(&pulses([0x02, 0x8C, 0x01, 0x5F, 0xEE]));
+        let data =
+            decode_pulses::<()>(DhtVariant::Dht22, &durations, POLLED_ONE_THRESHOLD).unwrap();
+        assert_eq!(data.humidity(), 65.2);
+        assert!(matches!(
+            decode_pulses::<()>(DhtVariant::Dht22, &durations[..39], POLLED_ONE_THRESHOLD),
+            Err(DhtError::ReadTimeout)
+        ));
+    }
+
+    /// Pin transactions of a read of the given bytes, the sensor holds the line
+    /// high for 40 polls to send a '1' and for 10 polls to send a '0'
+    fn transmission(bytes: [u8; 5]) -> Vec<Transaction> {
+        let mut transactions = vec![
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+            Transaction::get(State::Low),
+            Transaction::get(State::High),
+            Transaction::get(State::Low),
+        ];
+        for duration in pulses(bytes) {
+            let polls = if duration > POLLED_ONE_THRESHOLD {
+                40
+            } else {
+                10
+            };
+            transactions.push(Transaction::get(State::High));
+            transactions.extend((0..polls).map(|_| Transaction::get(State::High)));
+            transactions.push(Transaction::get(State::Low));
+        }
+        transactions
+    }
+
+    /// Pin transactions of a read of a sensor that never pulls the line low
+    fn no_answer() -> Vec<Transaction> {
+        let mut transactions = vec![Transaction::set(State::Low), Transaction::set(State::High)];
+        transactions.extend((0..=40).map(|_| Transaction::get(State::High)));
+        transactions
+    }
+
+    #[test]
+    fn reads_a_transmission() {
+        let mut pin = PinMock::new(&transmission([0x02, 0x8C, 0x01, 0x5F, 0xEE]));
+        let mut sensor = Dht22::new(NoopDelay::new(), pin.clone());
+        let data = sensor.read().unwrap();
+        assert_eq!(data.humidity(), 65.2);
+        assert_eq!(data.temperature(), 35.1);
+        pin.done();
+    }
+
+    #[test]
+    fn retries_a_failed_read() {
+        let mut pin =
+            PinMock::new(&[no_answer(), transmission([0x02, 0x8C, 0x01, 0x5F, 0xEE])].concat());
+        let mut sensor = Dht22::new(NoopDelay::new(), pin.clone());
+        let data = sensor.read_with_retry(2, 0).unwrap();
+        assert_eq!(data.temperature(), 35.1);
+        pin.done();
+    }
+
+    static NOW_MS: AtomicU64 = AtomicU64::new(0);
+
+    #[test]
+    fn waits_the_read_interval_after_a_failed_read() {
+        let mut pin = PinMock::new(&[no_answer(), no_answer()].concat());
+        let mut sensor = Dht22::new(NoopDelay::new(), pin.clone());
+        sensor.set_clock(|| NOW_MS.load(Ordering::Relaxed));
+        assert!(matches!(sensor.read(), Err(DhtError::NotFoundOnGPio)));
+        NOW_MS.store(MIN_READ_INTERVAL_MS as u64 - 1, Ordering::Relaxed);
+        assert!(matches!(sensor.read(), Err(DhtError::TooSoon)));
+        NOW_MS.store(MIN_READ_INTERVAL_MS as u64, Ordering::Relaxed);
+        assert!(matches!(sensor.read(), Err(DhtError::NotFoundOnGPio)));
+        pin.done();
+    }
+}
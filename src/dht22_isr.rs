@@ -0,0 +1,112 @@
+use crate::dht22::{decode_pulses, DhtError, DhtVariant, ReadoutData};
+use crate::sensors::{TempHum, TempHumSensor};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use embedded_hal::delay::DelayUs;
+use esp_idf_hal::delay::FreeRtos;
+use esp_idf_hal::gpio::{AnyIOPin, IOPin, InputOutput, InterruptType, PinDriver};
+use esp_idf_hal::peripheral::Peripheral;
+use esp_idf_svc::sys::{self, EspError};
+use std::sync::Arc;
+
+/// edges of the release of the line, the response and the 40 data bits
+const MAX_EDGES: usize = 96;
+/// the transmission takes less than 5 ms
+const TRANSMISSION_MS: u32 = 10;
+/// a '1' is sent as 70 us high, a '0' as 26 to 28 us high
+const ONE_THRESHOLD_US: u32 = 48;
+
+/// Timestamps in us of the edges recorded by the interrupt handler
+struct Edges {
+    times: [AtomicU32; MAX_EDGES],
+    count: AtomicUsize,
+}
+
+impl Edges {
+    /// Called from the interrupt handler only, so there is a single writer
+    fn record(&self, time: u32) {
+        let index = self.count.load(Ordering::Relaxed);
+        if index < MAX_EDGES {
+            self.times[index].store(time, Ordering::Relaxed);
+            self.count.store(index + 1, Ordering::Release);
+        }
+    }
+}
+
+/// Reads a sensor of the DHT family by recording the time of every edge in a
+/// gpio interrupt handler and decoding the bits afterwards, the task sleeps
+/// during the transmission instead of polling the pin every microsecond
+pub struct Dht22Isr<'d> {
+    pin: PinDriver<'d, AnyIOPin, InputOutput>,
+    edges: Arc<Edges>,
+    variant: DhtVariant,
+}
+
+impl<'d> Dht22Isr<'d> {
+    pub fn new(
+        pin: impl Peripheral<P = impl IOPin> + 'd,
+        variant: DhtVariant,
+    ) -> Result<Self, EspError> {
+        let mut pin = PinDriver::input_output_od(pin.into_ref().map_into::<AnyIOPin>())?;
+        pin.set_high()?;
+        pin.set_interrupt_type(InterruptType::AnyEdge)?;
+
+        let edges = Arc::new(Edges {
+            times: [(); MAX_EDGES].map(|_| AtomicU32::new(0)),
+            count: AtomicUsize::new(0),
+        });
+        let recorder = edges.clone();
+        let pin_number = pin.pin();
+        let on_edge = move || {
+            recorder.record(unsafe { sys::esp_timer_get_time() } as u32);
+            // the driver disables the interrupt before every call
+            unsafe { sys::gpio_intr_enable(pin_number) };
+        };
+        // safety: the handler only stores to atomics and reads the timer
+        unsafe { pin.subscribe(on_edge)? };
+        Ok(Self {
+            pin,
+            edges,
+            variant,
+        })
+    }
+
+    pub fn read(&mut self) -> Result<ReadoutData, DhtError<EspError>> {
+        // wake up the sensor, the dht11 needs at least 18 ms
+        self.edges.count.store(0, Ordering::Relaxed);
+        self.pin.set_low()?;
+        FreeRtos::delay_ms(20);
+        // the first edge recorded is the release of the line, from there on
+        // rising and falling edges alternate
+        self.pin.enable_interrupt()?;
+        self.pin.set_high()?;
+        FreeRtos::delay_ms(TRANSMISSION_MS);
+        self.pin.disable_interrupt()?;
+
+        let count = self.edges.count.load(Ordering::Acquire);
+        let times: Vec<u32> = self.edges.times[..count]
+            .iter()
+            .map(|time| time.load(Ordering::Relaxed))
+            .collect();
+        // the high pulses are the release of the line, the 80 us response of
+        // the sensor and the 40 data bits, so the data bits are the last ones
+        let durations: Vec<u32> = times
+            .chunks_exact(2)
+            .map(|high| high[1].wrapping_sub(high[0]))
+            .collect();
+        if durations.is_empty() {
+            return Err(DhtError::NotFoundOnGPio);
+        }
+        if durations.len() < 41 {
+            return Err(DhtError::ReadTimeout);
+        }
+        decode_pulses(self.variant, &durations, ONE_THRESHOLD_US)
+    }
+}
+
+impl TempHumSensor for Dht22Isr<'_> {
+    type Error = DhtError<EspError>;
+
+    fn read_temp_hum(&mut self, _delay: &mut dyn DelayUs) -> Result<TempHum, DhtError<EspError>> {
+        self.read().map(TempHum::from)
+    }
+}
@@ -0,0 +1,108 @@
+use crate::dht22::{decode_pulses, DhtError, DhtVariant, ReadoutData};
+use crate::sensors::{TempHum, TempHumSensor};
+use core::time::Duration;
+use embedded_hal::delay::DelayUs;
+use esp_idf_hal::delay::{FreeRtos, TickType};
+use esp_idf_hal::gpio::{AnyIOPin, IOPin, InputOutput, PinDriver};
+use esp_idf_hal::peripheral::Peripheral;
+use esp_idf_hal::rmt::config::ReceiveConfig;
+use esp_idf_hal::rmt::{PinState, Pulse, Receive, RmtChannel, RxRmtDriver};
+use esp_idf_svc::sys::EspError;
+
+/// ticks of 1 us with the 80 MHz APB clock
+const CLOCK_DIVIDER: u8 = 80;
+/// the longest pulse of the protocol is 80 us, the capture ends when the line
+/// stays high longer after the last bit
+const IDLE_THRESHOLD_US: u16 = 200;
+/// pulses shorter than 100 APB cycles (1.25 us) are dropped as glitches
+const FILTER_TICKS: u8 = 100;
+/// the transmission takes less than 5 ms
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(20);
+/// a '1' is sent as 70 us high, a '0' as 26 to 28 us high
+const ONE_THRESHOLD_US: u32 = 48;
+/// items of two pulses, the answer of the sensor has 43 of them
+const MAX_ITEMS: usize = 64;
+
+/// Reads a sensor of the DHT family by capturing the pulse train with the RMT
+/// peripheral and decoding it afterwards, so no core is kept busy polling the
+/// pin and interrupts cannot disturb the bit timings
+pub struct Dht22Rmt<'d> {
+    rx: RxRmtDriver<'d>,
+    pin: PinDriver<'d, AnyIOPin, InputOutput>,
+    variant: DhtVariant,
+}
+
+impl<'d> Dht22Rmt<'d> {
+    pub fn new<C: RmtChannel>(
+        channel: impl Peripheral<P = C> + 'd,
+        pin: impl Peripheral<P = impl IOPin> + 'd,
+        variant: DhtVariant,
+    ) -> Result<Self, EspError> {
+        let mut pin = pin.into_ref().map_into::<AnyIOPin>();
+        let config = ReceiveConfig::new()
+            .clock_divider(CLOCK_DIVIDER)
+            .idle_threshold(IDLE_THRESHOLD_US)
+            .filter_ticks_thresh(FILTER_TICKS)
+            .filter_en(true);
+        // the receiver is installed first, the pin driver then turns the
+        // input into an open drain output to wake up the sensor. safety: the
+        // receiver only reads the level of the pin driven by the pin driver
+        let rx = RxRmtDriver::new(
+            channel,
+            unsafe { pin.clone_unchecked() },
+            &config,
+            MAX_ITEMS,
+        )?;
+        let mut pin = PinDriver::input_output_od(pin)?;
+        pin.set_high()?;
+        Ok(Self { rx, pin, variant })
+    }
+
+    pub fn read(&mut self) -> Result<ReadoutData, DhtError<EspError>> {
+        // wake up the sensor, the dht11 needs at least 18 ms
+        self.pin.set_low()?;
+        FreeRtos::delay_ms(20);
+        self.rx.start()?;
+        self.pin.set_high()?;
+
+        let mut items = [(Pulse::zero(), Pulse::zero()); MAX_ITEMS];
+        let received = self
+            .rx
+            .receive(&mut items, TickType::from(RECEIVE_TIMEOUT).ticks());
+        self.rx.stop()?;
+        let len = match received? {
+            Receive::Read(len) => len,
+            Receive::Overflow(_) => return Err(DhtError::ReadTimeout),
+            Receive::Timeout => return Err(DhtError::NotFoundOnGPio),
+        };
+
+        // the high pulses are the release of the line, the 80 us response of
+        // the sensor and the 40 data bits, so the data bits are the last ones
+        let mut durations = [0u32; 2 * MAX_ITEMS];
+        let mut highs = 0;
+        for pulse in items[..len]
+            .iter()
+            .flat_map(|(first, second)| [first, second])
+        {
+            if pulse.pin_state == PinState::High && pulse.ticks.ticks() > 0 {
+                durations[highs] = pulse.ticks.ticks().into();
+                highs += 1;
+            }
+        }
+        if highs == 0 {
+            return Err(DhtError::NotFoundOnGPio);
+        }
+        if highs < 41 {
+            return Err(DhtError::ReadTimeout);
+        }
+        decode_pulses(self.variant, &durations[..highs], ONE_THRESHOLD_US)
+    }
+}
+
+impl TempHumSensor for Dht22Rmt<'_> {
+    type Error = DhtError<EspError>;
+
+    fn read_temp_hum(&mut self, _delay: &mut dyn DelayUs) -> Result<TempHum, DhtError<EspError>> {
+        self.read().map(TempHum::from)
+    }
+}
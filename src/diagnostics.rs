@@ -0,0 +1,146 @@
+use core::fmt;
+use esp_idf_svc::sys;
+use std::time::{Duration, Instant};
+
+/// Timing statistics of the read calls of a single sensor
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReadTiming {
+    reads: u32,
+    failures: u32,
+    timeouts: u32,
+    last: Duration,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+}
+
+impl ReadTiming {
+    /// Runs the given read and records how long it took and whether it failed
+    pub fn measure<T, E>(&mut self, read: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        let start = Instant::now();
+        let result = read();
+        self.record(start.elapsed(), result.is_ok());
+        result
+    }
+
+    fn record(&mut self, duration: Duration, success: bool) {
+        if self.reads == 0 || duration < self.min {
+            self.min = duration;
+        }
+        if duration > self.max {
+            self.max = duration;
+        }
+        self.reads += 1;
+        self.last = duration;
+        self.total += duration;
+        if !success {
+            self.failures += 1;
+        }
+    }
+
+    /// Counts a read that exceeded the timing tolerances of the sensor protocol
+    pub fn record_timeout(&mut self) {
+        self.timeouts += 1;
+    }
+
+    /// Returns the number of recorded reads
+    pub fn reads(&self) -> u32 {
+        self.reads
+    }
+
+    /// Returns the number of failed reads
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Returns the number of reads which exceeded the timing tolerances
+    pub fn timeouts(&self) -> u32 {
+        self.timeouts
+    }
+
+    /// Returns the duration of the most recent read
+    pub fn last(&self) -> Duration {
+        self.last
+    }
+
+    /// Returns the shortest read duration
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+
+    /// Returns the longest read duration
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Returns the average read duration
+    pub fn average(&self) -> Duration {
+        if self.reads == 0 {
+            return Duration::ZERO;
+        }
+        self.total / self.reads
+    }
+}
+
+/// Formats the statistics as json object without allocating
+impl fmt::Display for ReadTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{\"reads\": {}, \"failures\": {}, \"timeouts\": {}, \"last_us\": {}, \"min_us\": {}, \"avg_us\": {}, \"max_us\": {}}}",
+            self.reads,
+            self.failures,
+            self.timeouts,
+            self.last.as_micros(),
+            self.min.as_micros(),
+            self.average().as_micros(),
+            self.max.as_micros()
+        )
+    }
+}
+
+/// Heap and stack of the node in bytes, a small largest block compared to the
+/// free heap points to fragmentation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub free_heap: u32,
+    /// lowest free heap since startup
+    pub min_free_heap: u32,
+    pub largest_block: u32,
+    /// lowest free stack of the calling task since it started
+    pub stack_high_water: u32,
+}
+
+impl MemoryStats {
+    /// Samples the heap and the stack of the calling task
+    pub fn sample() -> Self {
+        unsafe {
+            Self {
+                free_heap: sys::esp_get_free_heap_size(),
+                min_free_heap: sys::esp_get_minimum_free_heap_size(),
+                largest_block: sys::heap_caps_get_largest_free_block(sys::MALLOC_CAP_DEFAULT)
+                    as u32,
+                stack_high_water: sys::uxTaskGetStackHighWaterMark(core::ptr::null_mut()),
+            }
+        }
+    }
+}
+
+/// Formats the statistics as json object without allocating
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{\"free_heap\": {}, \"min_free_heap\": {}, \"largest_block\": {}, \"stack_high_water\": {}}}",
+            self.free_heap, self.min_free_heap, self.largest_block, self.stack_high_water
+        )
+    }
+}
+
+/// Runtime diagnostics of the sensor drivers and the memory of the main task
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Diagnostics {
+    pub dht22: ReadTiming,
+    pub mhz19: ReadTiming,
+    pub memory: MemoryStats,
+}
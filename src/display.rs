@@ -0,0 +1,87 @@
+use crate::measurement::Measurement;
+use crate::ssd1306::{Controller, Oled, OledError};
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{Drawable, Point};
+use embedded_graphics::primitives::{Polyline, Primitive, PrimitiveStyle};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_hal::i2c::I2c;
+
+/// co2 values shown by the trend, two pixels apart over the display width
+pub const TREND_LEN: usize = 64;
+/// area of the trend below the text
+const TREND_TOP: i32 = 36;
+const TREND_HEIGHT: i32 = 27;
+
+/// Parses the configured display kind `none`, `ssd1306` or `sh1106`
+pub fn parse(kind: &str) -> Option<Option<Controller>> {
+    match kind {
+        "none" => Some(None),
+        "ssd1306" => Some(Some(Controller::Ssd1306)),
+        "sh1106" => Some(Some(Controller::Sh1106)),
+        _ => None,
+    }
+}
+
+/// Shows the latest measurement and the trend of the co2 concentration
+pub struct Display<HE, I: I2c<Error = HE>> {
+    oled: Oled<HE, I>,
+}
+
+impl<HE, I: I2c<Error = HE>> Display<HE, I> {
+    pub fn new(i2c: I, controller: Controller) -> Result<Self, OledError<HE>> {
+        let mut oled = Oled::new(i2c, controller);
+        oled.init()?;
+        oled.flush()?;
+        Ok(Self { oled })
+    }
+
+    /// Renders the measurement of the cycle, values not read are shown as `--`,
+    /// and the trend of up to `TREND_LEN` co2 values, the oldest first
+    pub fn show(&mut self, measurement: &Measurement, trend: &[i32]) -> Result<(), OledError<HE>> {
+        let large = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+        let small = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let co2 = match measurement.co2() {
+            Some(co2) => format!("{} ppm", co2),
+            None => "-- ppm".into(),
+        };
+        let temperature = match measurement.temperature() {
+            Some(temperature) => format!("{:.1} C", temperature),
+            None => "-- C".into(),
+        };
+        let humidity = match measurement.humidity() {
+            Some(humidity) => format!("{:.1} %", humidity),
+            None => "-- %".into(),
+        };
+
+        // drawing into the frame buffer cannot fail
+        self.oled.clear();
+        let _ = Text::with_baseline(&co2, Point::zero(), large, Baseline::Top).draw(&mut self.oled);
+        let _ = Text::with_baseline(&temperature, Point::new(0, 22), small, Baseline::Top)
+            .draw(&mut self.oled);
+        let _ = Text::with_baseline(&humidity, Point::new(72, 22), small, Baseline::Top)
+            .draw(&mut self.oled);
+        let _ = Polyline::new(&sparkline(trend))
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(&mut self.oled);
+        self.oled.flush()
+    }
+}
+
+/// Scales the trend to the height of its area, the newest value on the right
+fn sparkline(trend: &[i32]) -> Vec<Point> {
+    let trend = &trend[trend.len().saturating_sub(TREND_LEN)..];
+    let min = trend.iter().copied().min().unwrap_or_default();
+    let max = trend.iter().copied().max().unwrap_or_default();
+    let range = (max - min).max(1);
+    let start = (TREND_LEN - trend.len()) as i32 * 2;
+    trend
+        .iter()
+        .enumerate()
+        .map(|(i, co2)| {
+            let y = TREND_TOP + TREND_HEIGHT - (co2 - min) * TREND_HEIGHT / range;
+            Point::new(start + i as i32 * 2, y)
+        })
+        .collect()
+}
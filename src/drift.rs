@@ -0,0 +1,29 @@
+use std::time::{Duration, Instant};
+
+/// Cross-checks two co2 sensors measuring the same air and detects when
+/// they disagree beyond a threshold for a sustained period
+pub struct DriftMonitor {
+    threshold: i32,
+    period: Duration,
+    diverging_since: Option<Instant>,
+}
+
+impl DriftMonitor {
+    pub fn new(threshold: i32, period: Duration) -> Self {
+        Self {
+            threshold,
+            period,
+            diverging_since: None,
+        }
+    }
+
+    /// Records a pair of readings and returns true while the sensors drift apart
+    pub fn update(&mut self, primary: i32, secondary: i32) -> bool {
+        if (primary - secondary).abs() <= self.threshold {
+            self.diverging_since = None;
+            return false;
+        }
+        let since = *self.diverging_since.get_or_insert_with(Instant::now);
+        since.elapsed() >= self.period
+    }
+}
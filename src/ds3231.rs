@@ -0,0 +1,149 @@
+use core::fmt;
+use embedded_hal::i2c::I2c;
+
+pub const ADDRESS: u8 = 0x68;
+const REG_SECONDS: u8 = 0x00;
+const REG_STATUS: u8 = 0x0f;
+/// oscillator stop flag, set when the time was lost
+const STATUS_OSF: u8 = 0x80;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug)]
+pub enum Ds3231Error<HE> {
+    /// the registers do not contain a valid date
+    InvalidTime([u8; 7]),
+    /// Error of underlying IO
+    HalError(HE),
+}
+
+impl<HE> From<HE> for Ds3231Error<HE> {
+    fn from(error: HE) -> Self {
+        Ds3231Error::HalError(error)
+    }
+}
+
+impl<HE: fmt::Debug> fmt::Display for Ds3231Error<HE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Ds3231Error::*;
+        match self {
+            InvalidTime(regs) => write!(f, "Invalid time registers: {:02x?}", regs),
+            HalError(err) => write!(f, "HAL error: {:?}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<HE: fmt::Debug> std::error::Error for Ds3231Error<HE> {}
+
+/// Battery backed real time clock, the time is kept in UTC
+pub struct Ds3231<HE, I: I2c<Error = HE>> {
+    i2c: I,
+}
+
+impl<HE, I: I2c<Error = HE>> Ds3231<HE, I> {
+    pub fn new(i2c: I) -> Self {
+        Self { i2c }
+    }
+
+    /// Returns the time in seconds since the unix epoch or `None` if the clock
+    /// stopped since it was last set, e.g. because the battery was empty
+    pub fn read_time(&mut self) -> Result<Option<u64>, Ds3231Error<HE>> {
+        let mut status = [0u8; 1];
+        self.i2c.write_read(ADDRESS, &[REG_STATUS], &mut status)?;
+        if status[0] & STATUS_OSF != 0 {
+            return Ok(None);
+        }
+
+        let mut regs = [0u8; 7];
+        self.i2c.write_read(ADDRESS, &[REG_SECONDS], &mut regs)?;
+        let second = from_bcd(regs[0] & 0x7f);
+        let minute = from_bcd(regs[1] & 0x7f);
+        let hour = if regs[2] & 0x40 != 0 {
+            // 12 hour mode, bit 5 marks pm
+            from_bcd(regs[2] & 0x1f) % 12 + if regs[2] & 0x20 != 0 { 12 } else { 0 }
+        } else {
+            from_bcd(regs[2] & 0x3f)
+        };
+        let day = from_bcd(regs[4] & 0x3f);
+        let month = from_bcd(regs[5] & 0x1f);
+        let century = if regs[5] & 0x80 != 0 { 100 } else { 0 };
+        let year = 2000 + century + from_bcd(regs[6]) as u64;
+        if second > 59
+            || minute > 59
+            || hour > 23
+            || !(1..=31).contains(&day)
+            || !(1..=12).contains(&month)
+        {
+            return Err(Ds3231Error::InvalidTime(regs));
+        }
+
+        let days = days_from_civil(year, month as u64, day as u64);
+        Ok(Some(
+            days * SECONDS_PER_DAY + hour as u64 * 3600 + minute as u64 * 60 + second as u64,
+        ))
+    }
+
+    /// Sets the time in seconds since the unix epoch and clears the oscillator stop flag
+    pub fn set_time(&mut self, unix_time: u64) -> Result<(), Ds3231Error<HE>> {
+        let days = unix_time / SECONDS_PER_DAY;
+        let seconds = unix_time % SECONDS_PER_DAY;
+        let (year, month, day) = civil_from_days(days);
+        // 1970-01-01 was a thursday, the register counts 1..=7 starting on sunday
+        let weekday = (days + 4) % 7 + 1;
+        let century = if year >= 2100 { 0x80 } else { 0 };
+        self.i2c.write(
+            ADDRESS,
+            &[
+                REG_SECONDS,
+                to_bcd((seconds % 60) as u8),
+                to_bcd((seconds / 60 % 60) as u8),
+                to_bcd((seconds / 3600) as u8),
+                weekday as u8,
+                to_bcd(day as u8),
+                to_bcd(month as u8) | century,
+                to_bcd((year % 100) as u8),
+            ],
+        )?;
+
+        let mut status = [0u8; 1];
+        self.i2c.write_read(ADDRESS, &[REG_STATUS], &mut status)?;
+        self.i2c
+            .write(ADDRESS, &[REG_STATUS, status[0] & !STATUS_OSF])?;
+        Ok(())
+    }
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+/// Days since the unix epoch of a date in the gregorian calendar, valid from
+/// the year 2000 on
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Date in the gregorian calendar of the given days since the unix epoch
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,108 @@
+#[cfg(feature = "std")]
+use crate::dht22::{DhtError, DhtSensor, DhtVariant, MIN_READ_INTERVAL_MS};
+#[cfg(feature = "std")]
+use crate::sensors::{Retrying, TempHum, TempHumSensor};
+#[cfg(feature = "std")]
+use esp_idf_svc::hal::delay::{Delay, FreeRtos};
+#[cfg(feature = "std")]
+use esp_idf_svc::hal::gpio::{AnyIOPin, InputOutput, PinDriver};
+#[cfg(feature = "std")]
+use esp_idf_svc::sys::EspError;
+
+#[cfg(feature = "std")]
+type Dht<'d> = DhtSensor<EspError, Delay, PinDriver<'d, AnyIOPin, InputOutput>>;
+
+/// A DHT on its own pin in addition to the climate sensor of the node, e.g.
+/// in the next room or outside. Its readings are tagged with its id.
+#[cfg(feature = "std")]
+pub struct ExtraDht<'d> {
+    pub id: &'static str,
+    sensor: Retrying<Dht<'d>>,
+}
+
+#[cfg(feature = "std")]
+impl ExtraDht<'_> {
+    pub fn new(
+        id: &'static str,
+        pin: i32,
+        variant: DhtVariant,
+        critical_section: bool,
+        attempts: u32,
+        backoff_ms: u32,
+    ) -> Result<Self, EspError> {
+        let pin = PinDriver::input_output_od(unsafe { AnyIOPin::new(pin) })?;
+        let mut dht = DhtSensor::with_variant(Delay::new_default(), pin, variant);
+        if critical_section {
+            dht.set_critical_section(|read| esp_idf_svc::hal::interrupt::free(read));
+        }
+        Ok(Self {
+            id,
+            // the dht answers garbage when read again within its minimum interval
+            sensor: Retrying::new(dht, attempts, backoff_ms.max(MIN_READ_INTERVAL_MS)),
+        })
+    }
+
+    pub fn read(&mut self) -> Result<TempHum, DhtError<EspError>> {
+        self.sensor.read_temp_hum(&mut FreeRtos)
+    }
+}
+
+/// Parses the `id=pin` pairs separated by commas, e.g. `outside=26,attic=27`.
+/// The ids become part of the MQTT topics, so only letters, digits, `-` and
+/// `_` are allowed. Invalid entries and pins `is_free` rejects are skipped
+/// with a warning.
+pub fn parse(spec: &'static str, is_free: impl Fn(i32) -> bool) -> Vec<(&'static str, i32)> {
+    let mut sensors: Vec<(&'static str, i32)> = Vec::new();
+    for entry in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let parsed = entry
+            .split_once('=')
+            .and_then(|(id, pin)| Some((id.trim(), pin.trim().parse::<i32>().ok()?)));
+        match parsed {
+            Some((id, pin))
+                if is_valid_id(id)
+                    && is_free(pin)
+                    && !sensors
+                        .iter()
+                        .any(|(other_id, other_pin)| *other_id == id || *other_pin == pin) =>
+            {
+                sensors.push((id, pin))
+            }
+            _ => log::warn!("Invalid extra DHT {}, skipped", entry),
+        }
+    }
+    sensors
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_pairs() {
+        assert_eq!(
+            parse(" outside=26, attic = 27,", |_| true),
+            [("outside", 26), ("attic", 27)]
+        );
+        assert!(parse("", |_| true).is_empty());
+    }
+
+    #[test]
+    fn skips_invalid_and_duplicate_entries() {
+        let sensors = parse(
+            "outside=26,outside=27,attic=26,=25,cellar,bad id=32,home/x=33,bath=x,shed=4",
+            |pin| pin != 4,
+        );
+        assert_eq!(sensors, [("outside", 26)]);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::dht22::DhtError;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embedded_io::{ErrorType, Read, ReadReady, Write};
+use esp_idf_svc::sys::EspError;
+
+/// Faults which can be injected in debug builds to exercise the error handling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// the next DHT22 readout fails with a checksum error
+    DhtChecksum,
+    /// the first bytes of the next MH-Z19 response get lost
+    MHz19Drop,
+    /// the wifi connection is dropped
+    WifiDrop,
+    /// the given number of kilobytes are leaked from the heap
+    FillHeap(usize),
+}
+
+static DHT_CHECKSUM: AtomicBool = AtomicBool::new(false);
+static MHZ19_DROP: AtomicBool = AtomicBool::new(false);
+
+/// Arms a fault, sensor faults are injected on the next access of the sensor
+pub fn arm(fault: Fault) {
+    log::warn!("injecting fault {:?}", fault);
+    match fault {
+        Fault::DhtChecksum => DHT_CHECKSUM.store(true, Ordering::Relaxed),
+        Fault::MHz19Drop => MHZ19_DROP.store(true, Ordering::Relaxed),
+        Fault::WifiDrop | Fault::FillHeap(_) => {}
+    }
+}
+
+/// Replaces the readout result by a checksum error if the fault is armed
+pub fn inject_dht_checksum<T, E: From<DhtError<EspError>>>(result: Result<T, E>) -> Result<T, E> {
+    if DHT_CHECKSUM.swap(false, Ordering::Relaxed) {
+        return Err(DhtError::CheckSum(0x00, 0xff).into());
+    }
+    result
+}
+
+/// Leaks the given number of kilobytes to simulate a heap running full
+pub fn fill_heap(kilobytes: usize) {
+    let block = vec![0xa5u8; kilobytes * 1024].into_boxed_slice();
+    Box::leak(block);
+}
+
+/// UART wrapper which drops received bytes while the fault is armed
+pub struct FaultyUart<U> {
+    uart: U,
+}
+
+impl<U> FaultyUart<U> {
+    pub fn new(uart: U) -> Self {
+        Self { uart }
+    }
+}
+
+impl<U: ErrorType> ErrorType for FaultyUart<U> {
+    type Error = U::Error;
+}
+
+impl<U: Read> Read for FaultyUart<U> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let len = self.uart.read(buf)?;
+        if len > 0 && MHZ19_DROP.swap(false, Ordering::Relaxed) {
+            // lose the start of the frame like a late started read would
+            let dropped = len.min(2);
+            buf.copy_within(dropped..len, 0);
+            buf[len - dropped..len].fill(0);
+            return Ok(len - dropped);
+        }
+        Ok(len)
+    }
+}
+
+impl<U: ReadReady> ReadReady for FaultyUart<U> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.uart.read_ready()
+    }
+}
+
+impl<U: Write> Write for FaultyUart<U> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.uart.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.uart.flush()
+    }
+}
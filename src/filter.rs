@@ -0,0 +1,163 @@
+use crate::measurement::Measurement;
+
+/// Largest window of the moving average and the median, the values are kept
+/// in a fixed buffer so filtering does not allocate
+pub const MAX_WINDOW: usize = 16;
+
+/// How consecutive values of a sensor are smoothed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// values are passed on unchanged
+    None,
+    /// mean of the last n values
+    MovingAverage(usize),
+    /// exponentially weighted mean, the weight of the newest value in 0..=1
+    Exponential(f32),
+    /// median of the last n values, removes single spikes completely
+    Median(usize),
+}
+
+impl Smoothing {
+    /// Parses the configured filter kind `none`, `average`, `exponential` or
+    /// `median`, windows are limited to `MAX_WINDOW`
+    pub fn parse(kind: &str, window: usize, alpha: f32) -> Option<Self> {
+        let window = window.clamp(1, MAX_WINDOW);
+        match kind {
+            "none" => Some(Smoothing::None),
+            "average" => Some(Smoothing::MovingAverage(window)),
+            "exponential" if (0.0..=1.0).contains(&alpha) => Some(Smoothing::Exponential(alpha)),
+            "median" => Some(Smoothing::Median(window)),
+            _ => None,
+        }
+    }
+}
+
+/// Smooths the values of a single sensor
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+    smoothing: Smoothing,
+    values: [f32; MAX_WINDOW],
+    len: usize,
+    next: usize,
+    mean: Option<f32>,
+}
+
+impl Filter {
+    pub fn new(smoothing: Smoothing) -> Self {
+        Self {
+            smoothing,
+            values: [0.0; MAX_WINDOW],
+            len: 0,
+            next: 0,
+            mean: None,
+        }
+    }
+
+    /// Adds a new value and returns the smoothed one
+    pub fn apply(&mut self, value: f32) -> f32 {
+        match self.smoothing {
+            Smoothing::None => value,
+            Smoothing::MovingAverage(window) => {
+                self.push(value, window);
+                self.window().iter().sum::<f32>() / self.len as f32
+            }
+            Smoothing::Exponential(alpha) => {
+                let mean = match self.mean {
+                    Some(mean) => alpha * value + (1.0 - alpha) * mean,
+                    None => value,
+                };
+                self.mean = Some(mean);
+                mean
+            }
+            Smoothing::Median(window) => {
+                self.push(value, window);
+                let mut sorted = [0.0; MAX_WINDOW];
+                let sorted = &mut sorted[..self.len];
+                sorted.copy_from_slice(self.window());
+                sorted.sort_unstable_by(f32::total_cmp);
+                let middle = self.len / 2;
+                if self.len % 2 == 0 {
+                    (sorted[middle - 1] + sorted[middle]) / 2.0
+                } else {
+                    sorted[middle]
+                }
+            }
+        }
+    }
+
+    /// Stores the value in the ring buffer of the given window size
+    fn push(&mut self, value: f32, window: usize) {
+        self.values[self.next] = value;
+        self.next = (self.next + 1) % window;
+        self.len = (self.len + 1).min(window);
+    }
+
+    fn window(&self) -> &[f32] {
+        &self.values[..self.len]
+    }
+}
+
+/// Smooths all values of the measurements, invalid values leave the filters untouched
+pub struct MeasurementFilter {
+    co2: Filter,
+    temperature: Filter,
+    humidity: Filter,
+    pressure: Filter,
+}
+
+impl MeasurementFilter {
+    pub fn new(smoothing: Smoothing) -> Self {
+        Self {
+            co2: Filter::new(smoothing),
+            temperature: Filter::new(smoothing),
+            humidity: Filter::new(smoothing),
+            pressure: Filter::new(smoothing),
+        }
+    }
+
+    pub fn apply(&mut self, measurement: Measurement) -> Measurement {
+        let mut smoothed = measurement;
+        if let Some(co2) = measurement.co2() {
+            smoothed.set_co2(self.co2.apply(co2 as f32).round() as i32);
+        }
+        if let Some(temperature) = measurement.temperature() {
+            smoothed.set_temperature(self.temperature.apply(temperature));
+        }
+        if let Some(humidity) = measurement.humidity() {
+            smoothed.set_humidity(self.humidity.apply(humidity));
+        }
+        if let Some(pressure) = measurement.pressure() {
+            smoothed.set_pressure(self.pressure.apply(pressure));
+        }
+        smoothed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_of_an_even_window_is_the_mean_of_the_middle_values() {
+        let mut filter = Filter::new(Smoothing::Median(4));
+        assert_eq!(filter.apply(1.0), 1.0);
+        assert_eq!(filter.apply(2.0), 1.5);
+        assert_eq!(filter.apply(10.0), 2.0);
+        assert_eq!(filter.apply(4.0), 3.0);
+    }
+
+    #[test]
+    fn window_wraps_around() {
+        let mut median = Filter::new(Smoothing::Median(4));
+        let mut average = Filter::new(Smoothing::MovingAverage(3));
+        for value in [1.0, 2.0, 10.0, 4.0] {
+            median.apply(value);
+            average.apply(value);
+        }
+        // the 1.0 is replaced, the median of 2, 4, 5 and 10
+        assert_eq!(median.apply(5.0), 4.5);
+        // the 2.0 is replaced, the mean of 10, 4 and 7
+        assert_eq!(average.apply(7.0), 7.0);
+        assert_eq!(average.apply(7.0), 6.0);
+    }
+}
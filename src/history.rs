@@ -0,0 +1,110 @@
+use crate::measurement::Measurement;
+use std::collections::VecDeque;
+#[cfg(feature = "status-server")]
+use std::fmt::Write;
+use std::time::Instant;
+
+/// A measurement and whether it still has to be published
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    measurement: Measurement,
+    pending: bool,
+    /// when the measurement was taken, dates it once the clock is synchronized
+    taken: Instant,
+}
+
+/// The latest measurements, the oldest one is dropped once the configured
+/// length is reached. Measurements taken while the broker was unreachable are
+/// kept pending, so they can be published late once the connection is back.
+pub struct History {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Appends the measurement taken at `now` and returns the oldest one if it
+    /// had to be dropped while still pending
+    pub fn push(
+        &mut self,
+        measurement: Measurement,
+        pending: bool,
+        now: Instant,
+    ) -> Option<Measurement> {
+        let dropped = if self.entries.len() == self.capacity {
+            self.entries.pop_front()
+        } else {
+            None
+        };
+        self.entries.push_back(Entry {
+            measurement,
+            pending,
+            taken: now,
+        });
+        dropped
+            .filter(|entry| entry.pending)
+            .map(|entry| entry.measurement)
+    }
+
+    /// Sets the timestamp of the measurements taken before the clock was
+    /// synchronized, `unix_time` is the time at `now`
+    pub fn date(&mut self, unix_time: u64, now: Instant) {
+        for entry in &mut self.entries {
+            if entry.measurement.timestamp.is_none() {
+                let age = now.saturating_duration_since(entry.taken).as_secs();
+                entry.measurement.timestamp = Some(unix_time.saturating_sub(age));
+            }
+        }
+    }
+
+    /// Returns the measurements from the oldest to the newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Measurement> + '_ {
+        self.entries.iter().map(|entry| &entry.measurement)
+    }
+
+    /// Returns the last co2 values up to the given count, the oldest first
+    #[cfg(feature = "display")]
+    pub fn co2_trend(&self, count: usize) -> Vec<i32> {
+        let mut trend: Vec<i32> = self
+            .iter()
+            .rev()
+            .filter_map(Measurement::co2)
+            .take(count)
+            .collect();
+        trend.reverse();
+        trend
+    }
+
+    /// Returns up to `max` of the oldest pending measurements and marks them
+    /// as published, undated ones wait for the clock
+    pub fn take_pending(&mut self, max: usize) -> Vec<Measurement> {
+        self.entries
+            .iter_mut()
+            .filter(|entry| entry.pending && entry.measurement.timestamp.is_some())
+            .take(max)
+            .map(|entry| {
+                entry.pending = false;
+                entry.measurement
+            })
+            .collect()
+    }
+
+    /// Formats the measurements as json array, the oldest first
+    #[cfg(feature = "status-server")]
+    pub fn to_json(&self) -> String {
+        let mut json = String::with_capacity(self.entries.len() * 96 + 2);
+        json.push('[');
+        for (i, measurement) in self.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let _ = write!(json, "{}{}", separator, measurement);
+        }
+        json.push(']');
+        json
+    }
+}
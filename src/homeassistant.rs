@@ -0,0 +1,73 @@
+use crate::mqtt::MqttPublisher;
+use crate::payload::JsonStr;
+use embedded_svc::mqtt::client::QoS;
+
+/// A sensor entity announced to Home Assistant
+struct Entity {
+    quantity: &'static str,
+    /// key of the value in the json of `home/<location>/measurement`
+    key: &'static str,
+    device_class: &'static str,
+    unit: &'static str,
+}
+
+const ENTITIES: [Entity; 4] = [
+    Entity {
+        quantity: "temperature",
+        key: "temp",
+        device_class: "temperature",
+        unit: "°C",
+    },
+    Entity {
+        quantity: "humidity",
+        key: "hum",
+        device_class: "humidity",
+        unit: "%",
+    },
+    Entity {
+        quantity: "co2",
+        key: "co2",
+        device_class: "carbon_dioxide",
+        unit: "ppm",
+    },
+    Entity {
+        quantity: "pressure",
+        key: "pres",
+        device_class: "pressure",
+        unit: "hPa",
+    },
+];
+
+/// Publishes the retained discovery configs for the values of the measurement
+/// json, so Home Assistant creates the sensors of the node on its own. The
+/// pressure is only announced if the node measures it.
+pub fn announce(client: &mut MqttPublisher, location: &str, with_pressure: bool) {
+    let device = format!("co2-sensor-{}", location);
+    for entity in ENTITIES
+        .iter()
+        .filter(|entity| with_pressure || entity.quantity != "pressure")
+    {
+        let config = format!(
+            "{{\"name\": \"{}\", \"unique_id\": \"{}-{}\", \"state_topic\": \"home/{}/measurement\", \"value_template\": \"{{{{ value_json.{} }}}}\", \"device_class\": \"{}\", \"unit_of_measurement\": \"{}\", \"state_class\": \"measurement\", \"device\": {{\"identifiers\": [\"{}\"], \"name\": \"CO2 sensor {}\", \"sw_version\": \"{}\"}}}}",
+            entity.quantity,
+            JsonStr(&device),
+            entity.quantity,
+            JsonStr(location),
+            entity.key,
+            entity.device_class,
+            entity.unit,
+            JsonStr(&device),
+            JsonStr(location),
+            env!("CARGO_PKG_VERSION")
+        );
+        let publ_status = client.enqueue(
+            &format!("homeassistant/sensor/{}/{}/config", device, entity.quantity),
+            QoS::AtLeastOnce,
+            true,
+            config.as_bytes(),
+        );
+        if let Err(err) = publ_status {
+            log::warn!("error publishing discovery config: {:}", err);
+        }
+    }
+}
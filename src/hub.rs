@@ -0,0 +1,140 @@
+use crate::measurement::Measurement;
+use crate::payload::Payload;
+use anyhow::{anyhow, Result};
+use core::fmt::{self, Write};
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// UDP port the hub listens on for readings of its peers
+pub const PORT: u16 = 4711;
+
+/// Maximum length of a datagram sent to the hub
+const DATAGRAM_LEN: usize = 128;
+
+/// Encodes the measurement as datagram, e.g. `location=kitchen;co2=612;temperature=21.4`
+fn encode(location: &str, measurement: &Measurement) -> Result<Payload<DATAGRAM_LEN>, fmt::Error> {
+    let mut datagram = Payload::new();
+    write!(datagram, "location={}", location)?;
+    if let Some(co2) = measurement.co2() {
+        write!(datagram, ";co2={}", co2)?;
+    }
+    if let Some(temperature) = measurement.temperature() {
+        write!(datagram, ";temperature={}", temperature)?;
+    }
+    if let Some(humidity) = measurement.humidity() {
+        write!(datagram, ";humidity={}", humidity)?;
+    }
+    if let Some(pressure) = measurement.pressure() {
+        write!(datagram, ";pressure={}", pressure)?;
+    }
+    Ok(datagram)
+}
+
+/// The datagrams carry no time, the measurements of the peers have no timestamp
+fn decode(datagram: &str) -> Option<(String, Measurement)> {
+    let mut location = None;
+    let mut measurement = Measurement::new(None);
+    for field in datagram.trim().split(';') {
+        let (key, value) = field.split_once('=')?;
+        match key {
+            "location" => location = Some(value.to_string()),
+            "co2" => measurement.set_co2(value.parse().ok()?),
+            "temperature" => measurement.set_temperature(value.parse().ok()?),
+            "humidity" => measurement.set_humidity(value.parse().ok()?),
+            "pressure" => measurement.set_pressure(value.parse().ok()?),
+            _ => {}
+        }
+    }
+    Some((location?, measurement))
+}
+
+/// Latest readings of all rooms reporting to the hub
+#[derive(Debug, Clone, Default)]
+pub struct RoomTable {
+    rooms: Arc<Mutex<HashMap<String, (Measurement, bool)>>>,
+}
+
+impl RoomTable {
+    fn update(&self, location: String, measurement: Measurement) {
+        self.rooms
+            .lock()
+            .unwrap()
+            .insert(location, (measurement, true));
+    }
+
+    /// Returns the latest reading of every room
+    pub fn rooms(&self) -> Vec<(String, Measurement)> {
+        let rooms = self.rooms.lock().unwrap();
+        rooms
+            .iter()
+            .map(|(location, (measurement, _))| (location.clone(), *measurement))
+            .collect()
+    }
+
+    /// Returns the measurement received since the last call
+    pub fn take_updates(&self) -> Vec<(String, Measurement)> {
+        let mut rooms = self.rooms.lock().unwrap();
+        rooms
+            .iter_mut()
+            .filter(|(_, (_, updated))| *updated)
+            .map(|(location, (measurement, updated))| {
+                *updated = false;
+                (location.clone(), *measurement)
+            })
+            .collect()
+    }
+}
+
+/// Spawns a thread collecting the measurement sent by leaf nodes
+pub fn listen() -> Result<RoomTable> {
+    let socket = UdpSocket::bind(("0.0.0.0", PORT))?;
+    let table = RoomTable::default();
+    let rooms = table.clone();
+    thread::Builder::new()
+        .name("hub".into())
+        .stack_size(4096)
+        .spawn(move || {
+            let mut buf = [0u8; 128];
+            loop {
+                let (len, peer) = match socket.recv_from(&mut buf) {
+                    Ok(received) => received,
+                    Err(err) => {
+                        log::warn!("error receiving peer reading: {}", err);
+                        continue;
+                    }
+                };
+                let datagram = String::from_utf8_lossy(&buf[..len]);
+                match decode(&datagram) {
+                    Some((location, measurement)) => {
+                        log::info!(
+                            "measurement of {} from {}: {:?}",
+                            location,
+                            peer,
+                            measurement
+                        );
+                        rooms.update(location, measurement);
+                    }
+                    None => log::warn!("invalid reading from {}: {}", peer, datagram),
+                }
+            }
+        })?;
+    Ok(table)
+}
+
+/// Opens the socket to send readings to the hub, the host name is only
+/// resolved once here
+pub fn connect(hub_host: &str) -> Result<UdpSocket> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect((hub_host, PORT))?;
+    Ok(socket)
+}
+
+/// Sends the measurement of this node to the hub
+pub fn send(socket: &UdpSocket, location: &str, measurement: &Measurement) -> Result<()> {
+    let datagram = encode(location, measurement)
+        .map_err(|_| anyhow!("datagram exceeds {} bytes", DATAGRAM_LEN))?;
+    socket.send(datagram.as_bytes())?;
+    Ok(())
+}
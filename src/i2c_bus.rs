@@ -0,0 +1,45 @@
+use core::cell::RefCell;
+use embedded_hal::i2c::{ErrorType, I2c, Operation};
+
+/// Hands out the i2c bus to several drivers of the same thread, every
+/// transaction borrows the bus for its duration
+pub struct SharedI2c<'a, I> {
+    bus: &'a RefCell<I>,
+}
+
+impl<'a, I> SharedI2c<'a, I> {
+    pub fn new(bus: &'a RefCell<I>) -> Self {
+        Self { bus }
+    }
+}
+
+impl<I: ErrorType> ErrorType for SharedI2c<'_, I> {
+    type Error = I::Error;
+}
+
+impl<I: I2c> I2c for SharedI2c<'_, I> {
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.borrow_mut().read(address, read)
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.bus.borrow_mut().write(address, write)
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.bus.borrow_mut().write_read(address, write, read)
+    }
+
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.bus.borrow_mut().transaction(address, operations)
+    }
+}
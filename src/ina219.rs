@@ -0,0 +1,88 @@
+use core::fmt;
+use embedded_hal::i2c::I2c;
+
+pub const ADDRESS: u8 = 0x40;
+const REG_SHUNT_VOLTAGE: u8 = 0x01;
+const REG_BUS_VOLTAGE: u8 = 0x02;
+/// the bus voltage register flags an overflow of the power or current calculation
+const BUS_VOLTAGE_OVF: u16 = 0x01;
+
+#[derive(Debug)]
+pub enum Ina219Error<HE> {
+    /// the measured values are out of range
+    Overflow,
+    /// Error of underlying IO
+    HalError(HE),
+}
+
+impl<HE> From<HE> for Ina219Error<HE> {
+    fn from(error: HE) -> Self {
+        Ina219Error::HalError(error)
+    }
+}
+
+impl<HE: fmt::Debug> fmt::Display for Ina219Error<HE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Ina219Error::*;
+        match self {
+            Overflow => write!(f, "Measurement overflow"),
+            HalError(err) => write!(f, "HAL error: {:?}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<HE: fmt::Debug> std::error::Error for Ina219Error<HE> {}
+
+/// Voltage and current measured by the INA219
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerReading {
+    /// voltage at the load in V
+    pub voltage: f32,
+    /// current through the shunt in A
+    pub current: f32,
+}
+
+impl PowerReading {
+    /// Returns the power drawn by the load in W
+    pub fn power(&self) -> f32 {
+        self.voltage * self.current
+    }
+}
+
+/// Current and voltage monitor, the current is derived from the voltage
+/// across the shunt resistor so the calibration register is not used
+pub struct Ina219<HE, I: I2c<Error = HE>> {
+    i2c: I,
+    shunt_milliohm: u32,
+}
+
+impl<HE, I: I2c<Error = HE>> Ina219<HE, I> {
+    pub fn new(i2c: I, shunt_milliohm: u32) -> Self {
+        Self {
+            i2c,
+            shunt_milliohm,
+        }
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u16, Ina219Error<HE>> {
+        let mut value = [0u8; 2];
+        self.i2c.write_read(ADDRESS, &[register], &mut value)?;
+        Ok(u16::from_be_bytes(value))
+    }
+
+    pub fn read(&mut self) -> Result<PowerReading, Ina219Error<HE>> {
+        let bus_voltage = self.read_register(REG_BUS_VOLTAGE)?;
+        if bus_voltage & BUS_VOLTAGE_OVF != 0 {
+            return Err(Ina219Error::Overflow);
+        }
+        // signed with an LSB of 10 uV
+        let shunt_voltage = self.read_register(REG_SHUNT_VOLTAGE)? as i16;
+
+        Ok(PowerReading {
+            // bits 15..3 with an LSB of 4 mV
+            voltage: (bus_voltage >> 3) as f32 * 0.004,
+            current: shunt_voltage as f32 * 0.01 / self.shunt_milliohm as f32,
+        })
+    }
+}
@@ -0,0 +1,162 @@
+use crate::measurement::Measurement;
+use crate::sink::Sink;
+use crate::tls::ServerVerification;
+use anyhow::{bail, Result};
+use embedded_svc::http::client::Client;
+use embedded_svc::io::Write;
+use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
+use std::fmt::Write as _;
+
+/// Batches that failed are kept for the next attempt up to this many times
+/// the batch size, the oldest measurements are dropped beyond
+const MAX_BACKLOG_BATCHES: usize = 4;
+
+/// Writes the measurements in line protocol to the `/api/v2/write` endpoint
+/// of an InfluxDB v2, as an alternative to MQTT
+pub struct InfluxWriter {
+    url: String,
+    token: &'static str,
+    location: &'static str,
+    batch_size: usize,
+    batch: Vec<Measurement>,
+    verification: ServerVerification,
+}
+
+impl InfluxWriter {
+    pub fn new(
+        server: &str,
+        org: &str,
+        bucket: &str,
+        token: &'static str,
+        location: &'static str,
+        batch_size: usize,
+        verification: ServerVerification,
+    ) -> Self {
+        let batch_size = batch_size.max(1);
+        Self {
+            // the timestamps of the measurements are in seconds
+            url: format!(
+                "{}/api/v2/write?org={}&bucket={}&precision=s",
+                server.trim_end_matches('/'),
+                org,
+                bucket
+            ),
+            token,
+            location,
+            batch_size,
+            batch: Vec::with_capacity(batch_size),
+            verification,
+        }
+    }
+
+    fn write(&self, body: &str) -> Result<()> {
+        // nothing to write if the sensors delivered no values
+        if body.is_empty() {
+            return Ok(());
+        }
+        let connection = EspHttpConnection::new(&if self.url.starts_with("https://") {
+            self.verification.http_configuration()
+        } else {
+            Configuration::default()
+        })?;
+        let mut client = Client::wrap(connection);
+        let authorization = format!("Token {}", self.token);
+        let content_length = body.len().to_string();
+        let headers = [
+            ("Authorization", authorization.as_str()),
+            ("Content-Type", "text/plain; charset=utf-8"),
+            ("Content-Length", content_length.as_str()),
+        ];
+        let mut request = client.post(&self.url, &headers)?;
+        request.write_all(body.as_bytes())?;
+        let response = request.submit()?;
+        // the write endpoint answers 204 on success
+        if !(200..300).contains(&response.status()) {
+            bail!("influxdb responded with status {}", response.status());
+        }
+        Ok(())
+    }
+}
+
+impl Sink for InfluxWriter {
+    fn name(&self) -> &'static str {
+        "influx"
+    }
+
+    /// Adds the measurement and writes the batch once it is full. The server
+    /// stamps a line without timestamp with the time of the write, several of
+    /// them in one write would overwrite each other, so a measurement taken
+    /// before the clock is synchronized is written right away.
+    fn publish(&mut self, measurement: &Measurement) -> Result<()> {
+        if self.batch.len() >= self.batch_size * MAX_BACKLOG_BATCHES {
+            self.batch.remove(0);
+        }
+        self.batch.push(*measurement);
+        if self.batch.len() < self.batch_size && measurement.timestamp.is_some() {
+            return Ok(());
+        }
+        let written = self.write(&lines(self.location, &self.batch));
+        match written {
+            Ok(()) => self.batch.clear(),
+            // written later, a measurement without timestamp would get a wrong time
+            Err(_) => self
+                .batch
+                .retain(|measurement| measurement.timestamp.is_some()),
+        }
+        written
+    }
+
+    fn is_remote(&self) -> bool {
+        true
+    }
+}
+
+/// Formats one line per measurement, e.g.
+/// `climate,location=living_room co2=612i,temperature=21.4,humidity=48.2 1704110400`.
+/// Without a synchronized clock the timestamp is left out and the server
+/// takes the time of the write, a write holds at most one such line.
+fn lines(location: &str, measurements: &[Measurement]) -> String {
+    let mut body = String::with_capacity(measurements.len() * 96);
+    for measurement in measurements {
+        let mut fields = String::new();
+        if let Some(co2) = measurement.co2() {
+            let _ = write!(fields, ",co2={}i", co2);
+        }
+        if let Some(temperature) = measurement.temperature() {
+            let _ = write!(fields, ",temperature={:.1}", temperature);
+        }
+        if let Some(humidity) = measurement.humidity() {
+            let _ = write!(fields, ",humidity={:.1}", humidity);
+        }
+        if let Some(pressure) = measurement.pressure() {
+            let _ = write!(fields, ",pressure={:.1}", pressure);
+        }
+        // a line needs at least one field
+        if fields.is_empty() {
+            continue;
+        }
+        let _ = write!(
+            body,
+            "climate,location={} {}",
+            escape(location),
+            &fields[1..]
+        );
+        if let Some(timestamp) = measurement.timestamp {
+            let _ = write!(body, " {}", timestamp);
+        }
+        body.push('\n');
+    }
+    body
+}
+
+/// Escapes the characters line protocol reserves in tag values
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,106 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+//! Drivers of the sensors and the subsystems of the firmware, the binary sets
+//! them up and runs the measurement loop
+
+pub mod bme280;
+pub mod dht22;
+#[cfg(feature = "std")]
+pub mod dht22_isr;
+#[cfg(feature = "std")]
+pub mod dht22_rmt;
+pub mod ds3231;
+pub mod i2c_bus;
+pub mod ina219;
+pub mod mh_z19;
+pub mod scd;
+pub mod sensors;
+pub mod sht;
+#[cfg(feature = "display")]
+pub mod ssd1306;
+#[cfg(feature = "std")]
+pub mod uart;
+#[cfg(feature = "std")]
+pub mod wifi;
+
+#[cfg(feature = "std")]
+pub mod boot_report;
+#[cfg(all(feature = "std", feature = "scheduled-calibration"))]
+pub mod calibration;
+#[cfg(feature = "std")]
+pub mod climate;
+#[cfg(feature = "std")]
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod co2;
+#[cfg(feature = "std")]
+pub mod compensation;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(all(feature = "std", feature = "console"))]
+pub mod console;
+#[cfg(any(feature = "std", test))]
+pub mod derived;
+#[cfg(feature = "std")]
+pub mod diagnostics;
+#[cfg(all(feature = "std", feature = "display"))]
+pub mod display;
+#[cfg(all(feature = "std", feature = "redundancy"))]
+pub mod drift;
+#[cfg(any(feature = "std", test))]
+pub mod extra_dht;
+#[cfg(all(feature = "std", debug_assertions, feature = "console"))]
+pub mod faults;
+#[cfg(any(feature = "std", test))]
+pub mod filter;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod homeassistant;
+#[cfg(all(feature = "std", feature = "hub"))]
+pub mod hub;
+#[cfg(feature = "std")]
+pub mod influx;
+#[cfg(feature = "std")]
+pub mod mdns;
+#[cfg(any(feature = "std", test))]
+pub mod measurement;
+#[cfg(feature = "std")]
+pub mod mqtt;
+#[cfg(all(feature = "std", feature = "mqtt-mtls"))]
+pub mod mqtt_tls;
+#[cfg(feature = "std")]
+pub mod outdoor;
+pub mod payload;
+#[cfg(feature = "std")]
+pub mod postmortem;
+#[cfg(feature = "std")]
+pub mod power;
+#[cfg(feature = "std")]
+pub mod provisioning;
+#[cfg(feature = "std")]
+pub mod publish;
+#[cfg(feature = "std")]
+pub mod sampling;
+#[cfg(feature = "std")]
+pub mod schedule;
+#[cfg(feature = "std")]
+pub mod selftest;
+#[cfg(feature = "std")]
+pub mod sink;
+#[cfg(all(feature = "std", feature = "console"))]
+pub mod soak;
+#[cfg(any(feature = "std", test))]
+pub mod spool;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(all(feature = "std", feature = "status-server"))]
+pub mod status;
+#[cfg(feature = "std")]
+pub mod status_led;
+#[cfg(feature = "std")]
+pub mod tls;
+#[cfg(any(feature = "std", test))]
+pub mod urlencoded;
+#[cfg(target_os = "espidf")]
+pub mod ws2812;
@@ -1,42 +1,112 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use embedded_svc::mqtt::client::QoS;
-use esp_idf_svc::hal::delay::Delay;
+use esp_idf_svc::hal::delay::{Delay, FreeRtos};
 use esp_idf_svc::hal::{
-    gpio::AnyIOPin, gpio::PinDriver, peripherals::Peripherals, prelude::*, uart,
+    gpio::AnyIOPin, gpio::AnyOutputPin, gpio::PinDriver, i2c, peripherals::Peripherals, prelude::*,
+    rmt, uart,
 };
-use esp_idf_svc::mqtt::client::{EspMqttClient, MqttClientConfiguration};
-use std::{thread::sleep, time::Duration};
+#[cfg(feature = "console")]
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::{thread::sleep, time::Duration, time::Instant};
 
-use embedded_hal::digital::{OutputPin, PinState};
-
-mod wifi;
-use crate::wifi::wifi;
+use co2_sensor::dht22::{DhtError, DhtSensor, DhtVariant};
+use co2_sensor::dht22_isr::Dht22Isr;
+use co2_sensor::dht22_rmt::Dht22Rmt;
+use co2_sensor::ds3231::{self, Ds3231};
+use co2_sensor::i2c_bus::SharedI2c;
+use co2_sensor::ina219::{self, Ina219};
+use co2_sensor::mh_z19::{DetectionRange, MHz19, SensorModel, WarmupTracker};
+use co2_sensor::scd;
+use co2_sensor::sensors::{Retrying, TempHumSensor};
+use co2_sensor::sht::Precision;
+#[cfg(feature = "display")]
+use co2_sensor::ssd1306;
+#[cfg(all(feature = "console", feature = "display"))]
+use co2_sensor::ssd1306::Oled;
+use co2_sensor::uart::PolledUart;
+use co2_sensor::wifi::{self, WifiSupervisor};
 use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::hal::reset::ResetReason;
+use esp_idf_svc::hal::task::watchdog::{TWDTConfig, TWDTDriver};
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::EspError;
 
-mod dht22;
-use dht22::Dht22;
-
-mod mh_z19;
-use mh_z19::MHz19;
-
-#[toml_cfg::toml_config]
-pub struct Config {
-    #[default("")]
-    wifi_ssid: &'static str,
-    #[default("")]
-    wifi_psk: &'static str,
-    #[default("localhost")]
-    mqtt_host: &'static str,
-    #[default("")]
-    mqtt_user: &'static str,
-    #[default("")]
-    mqtt_pass: &'static str,
-}
+use co2_sensor::boot_report::BootReport;
+#[cfg(feature = "scheduled-calibration")]
+use co2_sensor::calibration::{CalibrationPolicy, CalibrationScheduler};
+use co2_sensor::climate::ClimateSource;
+use co2_sensor::clock::Clock;
+use co2_sensor::co2::Co2Source;
+use co2_sensor::compensation::Compensation;
+use co2_sensor::config::{self, AppConfig, ConfigStore, CONFIG};
+#[cfg(feature = "console")]
+use co2_sensor::console::{self, Command};
+use co2_sensor::diagnostics::{Diagnostics, MemoryStats};
+#[cfg(feature = "display")]
+use co2_sensor::display::{self, Display};
+#[cfg(feature = "redundancy")]
+use co2_sensor::drift::DriftMonitor;
+use co2_sensor::extra_dht::{self, ExtraDht};
+#[cfg(all(debug_assertions, feature = "console"))]
+use co2_sensor::faults::{self, Fault};
+use co2_sensor::filter::{MeasurementFilter, Smoothing};
+use co2_sensor::history::History;
+#[cfg(feature = "hub")]
+use co2_sensor::hub;
+use co2_sensor::influx::InfluxWriter;
+use co2_sensor::mqtt::{self, MqttPublisher};
+use co2_sensor::outdoor::{self, WeatherCache};
+use co2_sensor::payload::{JsonStr, Payload};
+use co2_sensor::power::{self, EnergyMeter, PowerHistory};
+use co2_sensor::publish::{
+    publish_extra_climate, publish_json, publish_measurement, publish_outdoor, publish_power,
+    publish_stats, report_fault,
+};
+use co2_sensor::sampling::SamplingCoordinator;
+use co2_sensor::schedule::{self, Schedule};
+use co2_sensor::selftest::SelfTestReport;
+#[cfg(feature = "display")]
+use co2_sensor::sink::DisplaySink;
+use co2_sensor::sink::{LogSink, MqttSink, Sinks};
+#[cfg(feature = "console")]
+use co2_sensor::soak;
+use co2_sensor::spool::Spool;
+use co2_sensor::stats::{FaultDetector, ReadOutcome, Stats};
+#[cfg(feature = "status-server")]
+use co2_sensor::status::{Status, StatusServer};
+use co2_sensor::status_led::{LedState, StatusLed};
+use co2_sensor::tls::ServerVerification;
+use co2_sensor::ws2812::{self, Ws2812};
+use co2_sensor::{homeassistant, mdns, postmortem, provisioning};
+use core::cell::RefCell;
+
+/// The MH-Z19 answers within a few milliseconds, a dead sensor must not block the loop
+const MHZ19_TIMEOUT_MS: u32 = 1000;
+/// Failed connection attempts before the node opens its provisioning access point
+const WIFI_CONNECT_ATTEMPTS: u32 = 10;
+/// The DHT22 needs 2 s between two reads, the MH-Z19 updates its value every 5 s
+const DHT_MIN_INTERVAL: Duration = Duration::from_secs(2);
+const MHZ19_MIN_INTERVAL: Duration = Duration::from_secs(5);
+/// Checksum errors and timeouts of the DHT are common, failed reads are repeated
+const DHT_READ_ATTEMPTS: u32 = 3;
+const DHT_RETRY_BACKOFF_MS: u32 = 2000;
+/// Longest time from the co2 to the climate reading of a cycle, the secondary
+/// MH-Z19 and the retries of the DHT run in between, plus the reads themselves
+const MAX_READ_SPREAD: Duration = Duration::from_millis(
+    ((DHT_READ_ATTEMPTS - 1) * DHT_RETRY_BACKOFF_MS + MHZ19_TIMEOUT_MS + 500) as u64,
+);
+/// Measurements missed by the broker are published late, a few per cycle
+const LATE_MEASUREMENTS_PER_CYCLE: usize = 32;
+/// Time a cycle may take beyond the measurement interval before the task
+/// watchdog restarts the node
+const WATCHDOG_MARGIN: Duration = Duration::from_secs(60);
 
 fn main() -> Result<()> {
     // It is necessary to call this function once. Otherwise some patches to the runtime
     // implemented by esp-idf-sys might not link properly. See https://github.com/esp-rs/esp-idf-template/issues/71
     esp_idf_svc::sys::link_patches();
+    let boot_time = Instant::now();
 
     // Bind the log crate to the ESP Logging facilities
     esp_idf_svc::log::EspLogger::initialize_default();
@@ -45,106 +115,1139 @@ fn main() -> Result<()> {
 
     let peripherals = Peripherals::take().unwrap();
 
-    // lets blink an LED while we are running
-    let mut led_pin = PinDriver::output(peripherals.pins.gpio2);
+    // The constant `CONFIG` is auto-generated by `toml_config`.
+    let app_config = CONFIG;
+    // credentials, interval and pins stored in the nvs override the compiled in ones
+    let nvs_partition = EspDefaultNvsPartition::take()?;
+    let mut config_store = ConfigStore::new(nvs_partition.clone())?;
+    let settings = config_store.load(AppConfig::defaults(&app_config));
+    if !settings.pins.is_valid() {
+        bail!("invalid pin mapping in cfg.toml: {:?}", settings.pins);
+    }
+    // a panic stores its reason before the restart, the boot report tells it
+    let last_panic = postmortem::install(nvs_partition.clone()).unwrap_or_else(|err| {
+        log::warn!("error installing the panic hook: {:}", err);
+        None
+    });
+
+    // counters in RTC memory tell whether the last run ended with a power loss
+    let reset_reason = ResetReason::get();
+    let power = PowerHistory::on_boot(reset_reason);
+    if power.power_loss {
+        log::warn!(
+            "Restarted after power loss, {} cycles completed before",
+            power.cycles_before_reset
+        );
+    }
+
+    // the led blinks a pattern telling the health of the node, starting with connecting
+    // safety: the pin mapping is checked to leave out the pins used elsewhere
+    let status_led = StatusLed::start(PinDriver::output(unsafe {
+        AnyIOPin::new(settings.pins.led)
+    })?)?;
+    // the optional WS2812 shows the co2 level as traffic light
+    let mut co2_light = if app_config.ws2812 {
+        Some(Ws2812::new(
+            rmt::TxRmtDriver::new(
+                peripherals.rmt.channel0,
+                // safety: the pin mapping is checked to leave out the pins used elsewhere
+                unsafe { AnyOutputPin::new(settings.pins.ws2812) },
+                &rmt::config::TransmitConfig::new().clock_divider(1),
+            )?,
+            app_config.ws2812_brightness,
+        ))
+    } else {
+        None
+    };
+
+    // the MH-Z19 can be replaced by a MH-Z14 on the same uart or one of the
+    // Sensirion sensors on the i2c bus
+    let co2_kind = match app_config.co2_sensor {
+        kind @ ("mhz19" | "mhz14" | "scd30" | "scd4x") => kind,
+        kind => {
+            log::warn!("Unsupported co2 sensor {}, using the MH-Z19", kind);
+            "mhz19"
+        }
+    };
+    let uses_mhz19 = matches!(co2_kind, "mhz19" | "mhz14");
+    let mhz19_model = if co2_kind == "mhz14" {
+        SensorModel::MHz14
+    } else {
+        SensorModel::MHz19
+    };
+    let climate_kind = match app_config.climate_sensor {
+        kind @ ("dht22" | "bme280" | "sht3x" | "sht4x") => kind,
+        kind => {
+            log::warn!("Unsupported climate sensor {}, using the DHT22", kind);
+            "dht22"
+        }
+    };
+    let uses_dht = climate_kind == "dht22";
+    let sht_precision = match app_config.sht_precision {
+        "high" => Precision::High,
+        "medium" => Precision::Medium,
+        "low" => Precision::Low,
+        precision => {
+            log::warn!("Unsupported SHT precision {}, using high", precision);
+            Precision::High
+        }
+    };
 
     // configure a uart port to read the co2 sensor data
     let config = uart::config::Config::default().baudrate(Hertz(9600));
 
     let uart: uart::UartDriver = uart::UartDriver::new(
         peripherals.uart1,
-        peripherals.pins.gpio33,
-        peripherals.pins.gpio32,
+        // safety: the pin mapping is checked to leave out the pins used elsewhere
+        unsafe { AnyIOPin::new(settings.pins.mhz19_tx) },
+        unsafe { AnyIOPin::new(settings.pins.mhz19_rx) },
         Option::<AnyIOPin>::None,
         Option::<AnyIOPin>::None,
         &config,
     )
     .unwrap();
-    let mut mhz19 = MHz19::new(uart);
-    mhz19.enable_auto_calibration(true)?;
+    let uart = PolledUart::new(uart);
+    #[cfg(all(debug_assertions, feature = "console"))]
+    let uart = faults::FaultyUart::new(uart);
+    let mut mhz19 = MHz19::with_model(uart, mhz19_model);
+    mhz19.set_trace(app_config.mhz19_trace);
+    // the sensor only needs to warm up if the reset cut its power as well
+    let warmup = match reset_reason {
+        ResetReason::PowerOn | ResetReason::Brownout if uses_mhz19 => WarmupTracker::new(),
+        _ => WarmupTracker::warmed_up(),
+    };
+    // scheduled zero point calibrations replace the automatic baseline correction
+    #[cfg(feature = "scheduled-calibration")]
+    let mut calibration = (app_config.calibration_weekday < 7 && uses_mhz19).then(|| {
+        CalibrationScheduler::new(
+            CalibrationPolicy {
+                weekday: app_config.calibration_weekday,
+                start_hour: app_config.calibration_start_hour,
+                end_hour: app_config.calibration_end_hour,
+                unoccupied: Duration::from_secs(app_config.calibration_unoccupied_hours * 60 * 60),
+                max_spread: app_config.calibration_max_spread,
+            },
+            settings.measurement_interval,
+        )
+    });
+    #[cfg(feature = "scheduled-calibration")]
+    let auto_calibration = calibration.is_none();
+    #[cfg(not(feature = "scheduled-calibration"))]
+    let auto_calibration = true;
+    if uses_mhz19 {
+        if let Err(err) =
+            mhz19.enable_auto_calibration(auto_calibration, MHZ19_TIMEOUT_MS, &mut FreeRtos)
+        {
+            log::warn!("error setting MH-Z19 auto calibration: {:}", err);
+        }
+    }
+    // the sensor keeps its range, it is only changed if configured
+    let mhz19_range = match app_config.mhz19_range {
+        0 => None,
+        ppm => {
+            let range = DetectionRange::from_ppm(ppm);
+            if range.is_none() {
+                log::warn!("Unsupported MH-Z19 range of {} ppm", ppm);
+            }
+            range
+        }
+    };
+    if let (Some(range), true) = (mhz19_range, uses_mhz19) {
+        if let Err(err) = mhz19.set_detection_range(range, &mut FreeRtos) {
+            log::warn!("error setting MH-Z19 range: {:}", err);
+        }
+    }
+
+    // an optional second co2 sensor on uart2 to detect when one of them drifts
+    #[cfg(feature = "redundancy")]
+    let mut mhz19_secondary = if app_config.mhz19_secondary {
+        let uart = uart::UartDriver::new(
+            peripherals.uart2,
+            unsafe { AnyIOPin::new(settings.pins.mhz19_secondary_tx) },
+            unsafe { AnyIOPin::new(settings.pins.mhz19_secondary_rx) },
+            Option::<AnyIOPin>::None,
+            Option::<AnyIOPin>::None,
+            &config,
+        )?;
+        let mut mhz19 = MHz19::with_model(PolledUart::new(uart), mhz19_model);
+        mhz19.set_trace(app_config.mhz19_trace);
+        if let Err(err) =
+            mhz19.enable_auto_calibration(auto_calibration, MHZ19_TIMEOUT_MS, &mut FreeRtos)
+        {
+            log::warn!("error setting secondary MH-Z19 auto calibration: {:}", err);
+        }
+        if let Some(range) = mhz19_range {
+            if let Err(err) = mhz19.set_detection_range(range, &mut FreeRtos) {
+                log::warn!("error setting secondary MH-Z19 range: {:}", err);
+            }
+        }
+        Some(mhz19)
+    } else {
+        None
+    };
+    #[cfg(feature = "redundancy")]
+    let mut drift = DriftMonitor::new(
+        app_config.drift_threshold_ppm,
+        Duration::from_secs(app_config.drift_period_min * 60),
+    );
 
     // sleep before talking to dht22 for first time
     sleep(Duration::from_millis(100));
 
-    // get io pin to talk to dht22
-    let delay = Delay::new_default();
-    let dht22_pin = PinDriver::input_output_od(peripherals.pins.gpio4).unwrap();
-    let mut dht22 = Dht22::new(delay, dht22_pin);
+    let dht_variant = if app_config.dht11 {
+        DhtVariant::Dht11
+    } else {
+        DhtVariant::Dht22
+    };
 
-    // The constant `CONFIG` is auto-generated by `toml_config`.
-    let app_config = CONFIG;
+    #[cfg(feature = "display")]
+    let display_controller = display::parse(app_config.display).unwrap_or_else(|| {
+        log::warn!(
+            "Unsupported display {}, no display used",
+            app_config.display
+        );
+        None
+    });
+    #[cfg(feature = "display")]
+    let uses_display = display_controller.is_some();
+    #[cfg(not(feature = "display"))]
+    let uses_display = false;
+    // the optional DS3231, INA219, OLED display and climate sensors share the i2c bus
+    let i2c_bus =
+        if app_config.ds3231 || app_config.ina219 || uses_display || !uses_mhz19 || !uses_dht {
+            Some(RefCell::new(i2c::I2cDriver::new(
+                peripherals.i2c0,
+                unsafe { AnyIOPin::new(settings.pins.i2c_sda) },
+                unsafe { AnyIOPin::new(settings.pins.i2c_scl) },
+                &i2c::config::Config::new().baudrate(Hertz(100_000)),
+            )?))
+        } else {
+            None
+        };
+    // the devices the selftest expects to acknowledge their address on the i2c bus
+    let mut i2c_devices: Vec<(&'static str, u8)> = Vec::new();
+    if app_config.ds3231 {
+        i2c_devices.push(("i2c_ds3231", ds3231::ADDRESS));
+    }
+    if app_config.ina219 {
+        i2c_devices.push(("i2c_ina219", ina219::ADDRESS));
+    }
+    #[cfg(feature = "display")]
+    if uses_display {
+        i2c_devices.push(("i2c_display", ssd1306::ADDRESS));
+    }
+    match climate_kind {
+        "bme280" => i2c_devices.push(("i2c_bme280", app_config.bme280_address)),
+        "sht3x" | "sht4x" => i2c_devices.push(("i2c_sht", app_config.sht_address)),
+        _ => {}
+    }
+    match co2_kind {
+        "scd30" => i2c_devices.push(("i2c_scd30", scd::SCD30_ADDRESS)),
+        "scd4x" => i2c_devices.push(("i2c_scd4x", scd::SCD4X_ADDRESS)),
+        _ => {}
+    }
+    // the DS3231 keeps the time while the network is unavailable
+    let mut rtc = match &i2c_bus {
+        Some(bus) if app_config.ds3231 => Some(Ds3231::new(SharedI2c::new(bus))),
+        _ => None,
+    };
+    // the INA219 measures the power drawn by the node or a connected load
+    let mut power_monitor = match &i2c_bus {
+        Some(bus) if app_config.ina219 => Some(Ina219::new(
+            SharedI2c::new(bus),
+            app_config.ina219_shunt_milliohm,
+        )),
+        _ => None,
+    };
+    let mut energy = EnergyMeter::default();
+    // the Sensirion sensors measure every 5 seconds, the latest value is read every cycle
+    let mut co2 = match i2c_bus
+        .as_ref()
+        .and_then(|bus| Co2Source::on_i2c(bus, co2_kind, auto_calibration))
+    {
+        Some(co2) => co2,
+        None => Co2Source::MHz19(mhz19),
+    };
+    // the BME280 and the SHTs replace the DHT22 on the data pin
+    let climate = match i2c_bus.as_ref().and_then(|bus| {
+        ClimateSource::on_i2c(
+            bus,
+            climate_kind,
+            app_config.bme280_address,
+            app_config.sht_address,
+            sht_precision,
+        )
+    }) {
+        Some(climate) => climate,
+        None => {
+            let dht22: Box<dyn TempHumSensor<Error = DhtError<EspError>>> = if app_config.dht_rmt {
+                // the RMT peripheral captures the pulses, no core is busy polling the pin
+                Box::new(Dht22Rmt::new(
+                    peripherals.rmt.channel1,
+                    unsafe { AnyIOPin::new(settings.pins.dht) },
+                    dht_variant,
+                )?)
+            } else if app_config.dht_isr {
+                // an interrupt handler records the edges, the task sleeps meanwhile
+                Box::new(Dht22Isr::new(
+                    unsafe { AnyIOPin::new(settings.pins.dht) },
+                    dht_variant,
+                )?)
+            } else {
+                // get io pin to talk to dht22
+                let delay = Delay::new_default();
+                let dht22_pin =
+                    PinDriver::input_output_od(unsafe { AnyIOPin::new(settings.pins.dht) })
+                        .unwrap();
+                let mut dht22 = DhtSensor::with_variant(delay, dht22_pin, dht_variant);
+                // wifi interrupts stretch the bit timings, so the bits are read with
+                // interrupts disabled
+                if app_config.dht_critical_section {
+                    dht22.set_critical_section(|read| esp_idf_svc::hal::interrupt::free(read));
+                }
+                // reads within 2 s of the last one return its value instead of garbage
+                dht22.set_clock(|| unsafe { esp_idf_svc::sys::esp_timer_get_time() } as u64 / 1000);
+                Box::new(dht22)
+            };
+            ClimateSource::Dht(dht22)
+        }
+    };
+    let mut climate = Retrying::new(climate, DHT_READ_ATTEMPTS, DHT_RETRY_BACKOFF_MS);
+    // additional DHTs on their own pins, e.g. for other rooms or outside
+    let mut extra_dhts: Vec<ExtraDht> =
+        extra_dht::parse(app_config.extra_dht, |pin| settings.pins.is_free(pin))
+            .into_iter()
+            .filter_map(|(id, pin)| {
+                ExtraDht::new(
+                    id,
+                    pin,
+                    dht_variant,
+                    app_config.dht_critical_section,
+                    DHT_READ_ATTEMPTS,
+                    DHT_RETRY_BACKOFF_MS,
+                )
+                .map_err(|err| log::warn!("error setting up DHT {}: {:}", id, err))
+                .ok()
+            })
+            .collect();
+    // the OLED display shows the measurement of every cycle
+    #[cfg(feature = "display")]
+    let display = match (&i2c_bus, display_controller) {
+        (Some(bus), Some(controller)) => match Display::new(SharedI2c::new(bus), controller) {
+            Ok(display) => Some(display),
+            Err(err) => {
+                log::warn!("error initializing the display: {:}", err);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    // outdoor weather to compare the indoor climate with, refreshed every outdoor_interval_min
+    let mut weather = WeatherCache::new(Duration::from_secs(app_config.outdoor_interval_min * 60));
 
     // Connect to the Wi-Fi network
     let sysloop = EspSystemEventLoop::take()?;
-    let wifi = wifi(
-        app_config.wifi_ssid,
-        app_config.wifi_psk,
-        peripherals.modem,
-        sysloop,
+    let mut wifi = wifi::station(peripherals.modem, sysloop.clone(), &settings.ip_settings())?;
+    let connected = wifi::connect(
+        &mut wifi,
+        &settings.wifi_networks(),
+        &settings.wifi_auth(),
+        app_config.wifi_scan,
+        Some(WIFI_CONNECT_ATTEMPTS),
+        sysloop.clone(),
+    );
+    // without working credentials the settings are entered in a browser instead, the
+    // restart also retries the network if none are entered within the timeout
+    if let Err(err) = &connected {
+        log::warn!("{:}", err);
+        provisioning::run(
+            &mut wifi,
+            sysloop.clone(),
+            &mut config_store,
+            &settings,
+            app_config.location,
+        )?;
+        // give the browser time to receive the response
+        sleep(Duration::from_secs(1));
+        esp_idf_svc::hal::reset::restart();
+    }
+    // the access points of the network found by the scan, for the status endpoint
+    #[cfg(feature = "status-server")]
+    let access_points = connected.unwrap_or_default();
+
+    // reconnects whenever the access point drops the connection
+    let wifi_supervisor =
+        WifiSupervisor::start(&sysloop, settings.wifi_networks(), settings.wifi_auth())?;
+
+    // the latest measurements for the status server, the display and late publishing
+    let history = Arc::new(Mutex::new(History::new(app_config.history_len)));
+    // pending measurements the history drops are kept in the nvs instead
+    let mut spool = if app_config.spool_len > 0 {
+        match Spool::new(nvs_partition.clone(), app_config.spool_len) {
+            Ok(spool) => Some(spool),
+            Err(err) => {
+                log::warn!("error opening the measurement spool: {:}", err);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // TLS connections verify the server with the configured CA certificate or the bundle
+    let verification = ServerVerification::init(&settings.ca_cert);
+
+    // measurements are also written to an InfluxDB v2 if a server is configured
+    let influx = (!app_config.influx_url.is_empty()).then(|| {
+        InfluxWriter::new(
+            app_config.influx_url,
+            app_config.influx_org,
+            app_config.influx_bucket,
+            app_config.influx_token,
+            app_config.location,
+            app_config.influx_batch,
+            verification,
+        )
+    });
+
+    // lets the node be polled directly on http://<address>/status, /metrics and /history
+    #[cfg(feature = "status-server")]
+    let status_server = if app_config.status_server {
+        Some(StatusServer::start(
+            boot_time,
+            app_config.location,
+            history.clone(),
+            access_points,
+        )?)
+    } else {
+        None
+    };
+    #[cfg(feature = "status-server")]
+    let serves_status = status_server.is_some();
+    #[cfg(not(feature = "status-server"))]
+    let serves_status = false;
+    // the endpoints are found as esp-co2-<location>.local without knowing the address
+    let _mdns = if app_config.mdns {
+        match mdns::advertise(app_config.location, serves_status) {
+            Ok(mdns) => Some(mdns),
+            Err(err) => {
+                log::warn!("error starting the mdns responder: {:}", err);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // synchronize the wall clock, times are shown in the configured time zone
+    let clock = Clock::new(app_config.timezone)?;
+    if let Some(rtc) = &mut rtc {
+        match rtc.read_time() {
+            Ok(Some(time)) => {
+                clock.set_time(time);
+                log::info!("Time set from DS3231 to {}", clock.now());
+            }
+            Ok(None) => log::warn!("DS3231 lost its time, waiting for SNTP"),
+            Err(err) => log::warn!("error reading DS3231: {:}", err),
+        }
+    }
+
+    // commands typed on the serial console are handled between measurements,
+    // the sender is kept here so the channel stays open if the console ends
+    #[cfg(feature = "console")]
+    let (commands_tx, commands) = mpsc::channel();
+    // commands published to home/<location>/cmd are handled like the ones of the console
+    #[cfg(feature = "console")]
+    let command_topic = app_config.mqtt_commands.then(|| {
+        let remote_commands = commands_tx.clone();
+        mqtt::CommandTopic {
+            topic: format!("home/{}/cmd", app_config.location),
+            handler: Box::new(move |payload| match Command::parse_remote(payload) {
+                Some(command) => {
+                    let _ = remote_commands.send(command);
+                }
+                None => log::warn!("unsupported remote command: {}", payload),
+            }),
+        }
+    });
+    #[cfg(not(feature = "console"))]
+    let command_topic = None;
+
+    // in privacy mode nothing leaves the node, readings are only logged locally
+    let mut client = MqttPublisher::connect(
+        &settings.mqtt_host,
+        &settings.mqtt_user,
+        &settings.mqtt_pass,
+        app_config.mqtt_tls.then_some(verification),
+        command_topic,
+        app_config.privacy_mode,
     )?;
 
-    let broker_url = format!(
-        "mqtt://{}:{}@{}",
-        app_config.mqtt_user, app_config.mqtt_pass, app_config.mqtt_host
+    // first value is usually broken
+    let _ = co2.read_ppm();
+    #[cfg(feature = "redundancy")]
+    if let Some(secondary) = &mut mhz19_secondary {
+        let _ = secondary.read_co2_timeout(MHZ19_TIMEOUT_MS, &mut FreeRtos);
+    }
+
+    // probe the sensors and tell the broker how the node came up, the message is
+    // queued until the connection to the broker is established
+    let mut sensors = SelfTestReport::default();
+    sensors.check(
+        climate.sensor().name(),
+        climate.sensor().read_temp_hum(&mut FreeRtos),
+    );
+    sensors.check(co2.name(), co2.read_ppm());
+    if let Some(bus) = &i2c_bus {
+        for &(name, address) in &i2c_devices {
+            sensors.probe_i2c(name, &mut SharedI2c::new(bus), address);
+        }
+    }
+    let boot_report = BootReport {
+        reset_reason,
+        sensors,
+        config_checksum: config::checksum(&app_config, &settings),
+        time_synced: clock.is_synced(),
+        power,
+        last_panic,
+        memory: MemoryStats::sample(),
+    };
+    let boot_msg = boot_report.to_json();
+    log::info!("Boot report: {}", boot_msg);
+    let publ_status = client.enqueue(
+        &format!("home/data/boot/{}", app_config.location),
+        QoS::AtLeastOnce,
+        true,
+        boot_msg.as_bytes(),
     );
-    let mqtt_config = MqttClientConfiguration::default();
-    let mut client = EspMqttClient::new(&broker_url, &mqtt_config, move |_message_event| {
-        // left empty on purpose
-    })?;
+    if let Err(err) = publ_status {
+        log::warn!("error publishing boot report: {:}", err);
+    }
+    if app_config.homeassistant_discovery {
+        homeassistant::announce(&mut client, app_config.location, climate_kind == "bme280");
+    }
 
-    // first value is usually broken
-    let _ = mhz19.read_co2();
+    // the outputs of the measurement of every cycle, each one is enabled by `outputs`
+    let mut sinks = Sinks::default();
+    sinks.add(app_config.outputs, LogSink);
+    sinks.add(
+        app_config.outputs,
+        MqttSink::new(
+            client.clone(),
+            app_config.location,
+            app_config.legacy_topics,
+        ),
+    );
+    if let Some(influx) = influx {
+        sinks.add(app_config.outputs, influx);
+    }
+    #[cfg(feature = "display")]
+    if let Some(display) = display {
+        sinks.add(
+            app_config.outputs,
+            DisplaySink::new(display, history.clone()),
+        );
+    }
+    for (name, interval) in schedule::parse_intervals(app_config.output_intervals) {
+        sinks.set_interval(name, interval);
+    }
+    let mqtt_output = sinks.is_enabled("mqtt");
+
+    let mut diagnostics = Diagnostics::default();
+
+    // a hub collects the readings of leaf nodes and republishes them
+    #[cfg(feature = "hub")]
+    let rooms = if app_config.hub_mode {
+        Some(hub::listen()?)
+    } else {
+        None
+    };
+    #[cfg(feature = "hub")]
+    let hub_socket = if app_config.hub_host.is_empty() {
+        None
+    } else {
+        Some(hub::connect(app_config.hub_host)?)
+    };
+
+    // sensors with a longer interval than the cycle repeat their last reading in between
+    let mut co2_schedule = Schedule::new(Duration::from_secs(app_config.co2_interval_s).max(
+        if uses_mhz19 {
+            MHZ19_MIN_INTERVAL
+        } else {
+            Duration::ZERO
+        },
+    ));
+    let mut climate_schedule = Schedule::new(
+        Duration::from_secs(app_config.climate_interval_s).max(if climate_kind == "dht22" {
+            DHT_MIN_INTERVAL
+        } else {
+            Duration::ZERO
+        }),
+    );
+    let mut last_co2 = None;
+    let mut last_climate = None;
+    // error counters per sensor, published at their own interval
+    let mut stats = Stats::default();
+    let mut stats_schedule = Schedule::new(Duration::from_secs(app_config.stats_interval_min * 60));
+    // a sensor failing in a row is only probed now and then until it recovers
+    let fault_probe_interval = Duration::from_secs(app_config.fault_probe_min * 60);
+    let mut co2_fault = FaultDetector::new(app_config.fault_threshold, fault_probe_interval);
+    let mut climate_fault = FaultDetector::new(app_config.fault_threshold, fault_probe_interval);
+
+    // values of one cycle are only published together if they were read close together,
+    // a retried DHT read must not make the co2 value of the same cycle stale
+    let mut sampling = SamplingCoordinator::new(
+        Duration::from_millis(app_config.max_sample_age_ms).max(MAX_READ_SPREAD),
+    );
+    // smoothing of the readings before they are logged and published
+    let smoothing = Smoothing::parse(
+        app_config.filter,
+        app_config.filter_window,
+        app_config.filter_alpha,
+    )
+    .unwrap_or_else(|| {
+        log::warn!(
+            "Unsupported filter {}, readings are not smoothed",
+            app_config.filter
+        );
+        Smoothing::None
+    });
+    let mut filter = MeasurementFilter::new(smoothing);
+    // the Sensirion sensors compensate their readings themselves
+    let compensation = match Compensation::parse(
+        app_config.co2_compensation,
+        app_config.co2_compensation_ppm_per_c,
+    ) {
+        Some(compensation) if uses_mhz19 => compensation,
+        Some(Compensation::None) => Compensation::None,
+        Some(_) => {
+            log::warn!("The co2 compensation only applies to the MH-Z19");
+            Compensation::None
+        }
+        None => {
+            log::warn!(
+                "Unsupported co2 compensation {}, readings are not compensated",
+                app_config.co2_compensation
+            );
+            Compensation::None
+        }
+    };
+
+    #[cfg(feature = "console")]
+    console::spawn(commands_tx.clone())?;
+
+    // restarts the node if a sensor read or a network call hangs the loop
+    let mut watchdog_driver = TWDTDriver::new(
+        peripherals.twdt,
+        &TWDTConfig {
+            duration: settings.measurement_interval + WATCHDOG_MARGIN,
+            panic_on_trigger: true,
+            ..Default::default()
+        },
+    )?;
+    let mut watchdog = watchdog_driver.watch_current_task()?;
+
+    // the remote commands change the interval and the thresholds at runtime
+    #[cfg_attr(not(feature = "console"), allow(unused_mut))]
+    let (mut measurement_interval, mut co2_warn_ppm, mut co2_alert_ppm) = (
+        settings.measurement_interval,
+        app_config.co2_warn_ppm,
+        app_config.co2_alert_ppm,
+    );
 
     loop {
+        if let Err(err) = watchdog.feed() {
+            log::warn!("error feeding the watchdog: {:}", err);
+        }
         println!("Reading data");
-        let wifi_connected = wifi.is_connected();
-        match wifi_connected {
-            Ok(conn) => {
-                let state = if conn { PinState::High } else { PinState::Low };
-                led_pin.as_mut().unwrap().set_state(state)?;
+        if clock.is_synced() {
+            log::info!("Local time {}", clock.now());
+        }
+        // the network time is written back to the DS3231 after every sync
+        if let (Some(rtc), true) = (&mut rtc, clock.take_sntp_update()) {
+            if let Err(err) = rtc.set_time(clock.unix_time()) {
+                log::warn!("error setting DS3231: {:}", err);
             }
-            Err(err) => log::warn!("Wifi not connected {}", err),
         }
-
         // read co2 concentration
-        let co2_result = mhz19.read_co2();
+        let co2_result = if co2_schedule.is_due(Instant::now())
+            && co2_fault.should_read(&stats.co2, Instant::now())
+        {
+            let result = diagnostics.mhz19.measure(|| co2.read_ppm());
+            stats.co2.record(ReadOutcome::of(&result));
+            if let Some(faulted) = co2_fault.update(&mut stats.co2) {
+                report_fault(&mut client, app_config.location, "co2", faulted);
+            }
+            last_co2 = result.as_ref().ok().copied();
+            result
+        } else {
+            last_co2.ok_or_else(|| anyhow::anyhow!("no co2 reading since the last failure"))
+        };
+        // readings during the warm-up are only logged, not published
+        let warmed_up = warmup.is_ready(boot_time.elapsed());
+        let co2_failed = co2_result.is_err();
+        #[cfg(feature = "redundancy")]
+        let primary_co2 = co2_result.as_ref().ok().copied().filter(|_| warmed_up);
         match co2_result {
-            Ok(co2) => {
-                let co2_msg = format!("{{\"location\": \"esp-bedroom\", \"co2\": {:}}}", co2);
-                let publ_status =
-                    client.publish("home/data/co2", QoS::AtLeastOnce, false, co2_msg.as_bytes());
-                match publ_status {
-                    Ok(_) => {}
-                    Err(err) => log::warn!("error publishing CO2 data: {:}", err),
-                };
+            Ok(co2) if !warmed_up => {
+                log::info!("Provisional CO2 reading of {} ppm, MH-Z19 warming up", co2)
             }
+            Ok(co2_ppm) => {
+                sampling.record_co2(co2_ppm);
+                // scheduled calibrations are only set up for the MH-Z19
+                #[cfg(feature = "scheduled-calibration")]
+                if let (Some(calibration), Some(mhz19)) = (&mut calibration, co2.mhz19()) {
+                    calibration.record(co2_ppm);
+                    let now = clock.now();
+                    if clock.is_synced() && calibration.is_due(&now) {
+                        log::info!(
+                            "Starting scheduled zero point calibration at {} ppm",
+                            co2_ppm
+                        );
+                        match mhz19.calibrate_zero_point(&mut FreeRtos) {
+                            Ok(()) => calibration.calibrated(&now),
+                            Err(err) => log::warn!("error calibrating zero point: {:}", err),
+                        }
+                        #[cfg(feature = "redundancy")]
+                        if let Some(secondary) = &mut mhz19_secondary {
+                            if let Err(err) = secondary.calibrate_zero_point(&mut FreeRtos) {
+                                log::warn!("error calibrating secondary zero point: {:}", err);
+                            }
+                        }
+                    }
+                }
+            }
+            Err(_) if stats.co2.faulted => {}
             Err(err) => log::warn!("error reading CO2 data: {:}", err),
         }
 
+        // cross-check against the secondary co2 sensor
+        #[cfg(feature = "redundancy")]
+        if let (Some(secondary), Some(co2)) = (&mut mhz19_secondary, primary_co2) {
+            match secondary.read_co2_timeout(MHZ19_TIMEOUT_MS, &mut FreeRtos) {
+                Ok(co2_secondary) => {
+                    let drifting = drift.update(co2, co2_secondary);
+                    if drifting {
+                        log::warn!(
+                            "CO2 sensors drifted apart: {} vs {} ppm",
+                            co2,
+                            co2_secondary
+                        );
+                    }
+                    let drift_msg = Payload::<192>::format(format_args!(
+                        "{{\"location\": \"{}\", \"co2\": {}, \"co2_secondary\": {}, \"divergence\": {}, \"drift\": {}}}",
+                        JsonStr(app_config.location),
+                        co2,
+                        co2_secondary,
+                        co2 - co2_secondary,
+                        drifting
+                    ));
+                    match drift_msg {
+                        Ok(drift_msg) => {
+                            let publ_status = client.publish(
+                                "home/data/co2_redundancy",
+                                QoS::AtLeastOnce,
+                                false,
+                                drift_msg.as_bytes(),
+                            );
+                            if let Err(err) = publ_status {
+                                log::warn!("error publishing CO2 redundancy data: {:}", err);
+                            }
+                        }
+                        Err(err) => log::warn!("error encoding CO2 redundancy data: {:}", err),
+                    }
+                }
+                Err(err) => log::warn!("error reading secondary CO2 data: {:}", err),
+            }
+        }
+
         // read temperature and humidity
-        let hum_and_temp = dht22.read();
+        let hum_and_temp = if climate_schedule.is_due(Instant::now())
+            && climate_fault.should_read(&stats.climate, Instant::now())
+        {
+            let result = diagnostics
+                .dht22
+                .measure(|| climate.read_temp_hum(&mut FreeRtos));
+            stats.climate.record(ReadOutcome::of(&result));
+            if let Some(faulted) = climate_fault.update(&mut stats.climate) {
+                report_fault(&mut client, app_config.location, "climate", faulted);
+            }
+            last_climate = result.as_ref().ok().copied();
+            result
+        } else {
+            last_climate.ok_or_else(|| anyhow::anyhow!("no climate reading since the last failure"))
+        };
+        #[cfg(all(debug_assertions, feature = "console"))]
+        let hum_and_temp = faults::inject_dht_checksum(hum_and_temp);
+        if let Some(DhtError::NotFoundOnGPio | DhtError::ReadTimeout) = hum_and_temp
+            .as_ref()
+            .err()
+            .and_then(|err| err.downcast_ref::<DhtError<EspError>>())
+        {
+            diagnostics.dht22.record_timeout();
+        }
+        let dht_failed = hum_and_temp.is_err();
         match hum_and_temp {
-            Ok(val) => {
-                let ambient_data_msg = format!(
-                    "{{\"temperature\": {:}, \"humidity\": {:}, \"pressure\": {:}, \"location\": \"esp-bedroom\"}}",
-                    val.temperature(),
-                    val.humidity(),
-                    0
+            Ok(val) => sampling.record_climate(val),
+            Err(_) if stats.climate.faulted => {}
+            Err(err) => log::warn!("{}", err),
+        }
+
+        let measurement = filter.apply(
+            compensation.apply(sampling.snapshot(clock.is_synced().then(|| clock.unix_time()))),
+        );
+        status_led.set(LedState::from_health(
+            wifi_supervisor.is_connected(),
+            client.is_connected() || client.is_local_only(),
+            co2_failed || dht_failed,
+            measurement.co2().map_or(false, |co2| co2 >= co2_alert_ppm),
+        ));
+        if let Some(light) = co2_light.as_mut() {
+            let color = ws2812::traffic_light(measurement.co2(), co2_warn_ppm, co2_alert_ppm);
+            if let Err(err) = light.set(color) {
+                log::warn!("error setting the co2 light: {:}", err);
+            }
+        }
+        // measurements the broker misses are kept pending, none in privacy mode
+        let pending = mqtt_output
+            && !client.is_connected()
+            && !client.is_local_only()
+            && !measurement.is_empty();
+        // measurements taken before the clock was synchronized are dated late
+        let dropped = {
+            let mut history = history.lock().unwrap();
+            if clock.is_synced() {
+                history.date(clock.unix_time(), Instant::now());
+            }
+            history.push(measurement, pending, Instant::now())
+        };
+        match (dropped, spool.as_mut()) {
+            // the spool survives restarts, a measurement without time can not be
+            // dated after them
+            (Some(dropped), _) if dropped.timestamp.is_none() => {
+                log::warn!("Dropping unsent measurement without time {}", dropped)
+            }
+            (Some(dropped), Some(spool)) => {
+                if let Err(err) = spool.push(&dropped) {
+                    log::warn!("error spooling measurement: {:}", err);
+                }
+            }
+            _ => {}
+        }
+        if client.is_local_only() {
+            log::info!("Privacy mode, not publishing {:?}", measurement);
+        }
+        if mqtt_output && client.is_connected() {
+            // the spooled measurements are older than the pending ones of the history
+            let mut late = match spool.as_mut() {
+                Some(spool) => spool
+                    .take(LATE_MEASUREMENTS_PER_CYCLE)
+                    .unwrap_or_else(|err| {
+                        log::warn!("error reading spooled measurements: {:}", err);
+                        Vec::new()
+                    }),
+                None => Vec::new(),
+            };
+            late.extend(
+                history
+                    .lock()
+                    .unwrap()
+                    .take_pending(LATE_MEASUREMENTS_PER_CYCLE - late.len()),
+            );
+            for late in &late {
+                publish_json(&mut client, app_config.location, late);
+            }
+        }
+        sinks.publish(&measurement, client.is_local_only());
+        for dht in &mut extra_dhts {
+            match dht.read() {
+                Ok(val) => publish_extra_climate(&mut client, app_config.location, dht.id, val),
+                Err(err) => log::warn!("error reading DHT {}: {:}", dht.id, err),
+            }
+        }
+
+        #[cfg(feature = "hub")]
+        if let (Some(socket), false) = (&hub_socket, client.is_local_only()) {
+            if let Err(err) = hub::send(socket, app_config.location, &measurement) {
+                log::warn!("error sending reading to hub: {:}", err);
+            }
+        }
+
+        #[cfg(feature = "hub")]
+        if let Some(rooms) = &rooms {
+            for (location, room) in rooms.take_updates() {
+                publish_measurement(&mut client, &location, &room, app_config.legacy_topics);
+            }
+            for (location, room) in rooms.rooms() {
+                log::info!("{}: {:?}", location, room);
+            }
+        }
+
+        // the weather api is not asked in privacy mode, the request reveals the location
+        if !app_config.outdoor_latitude.is_empty() && !client.is_local_only() {
+            if weather.is_due(Instant::now()) {
+                let fetched =
+                    outdoor::fetch(app_config.outdoor_latitude, app_config.outdoor_longitude);
+                weather.update(fetched, Instant::now());
+            }
+            if let (Some(outdoor), Some(temperature), Some(humidity)) = (
+                weather.latest(),
+                measurement.temperature(),
+                measurement.humidity(),
+            ) {
+                publish_outdoor(
+                    &mut client,
+                    app_config.location,
+                    temperature,
+                    humidity,
+                    outdoor,
                 );
+            }
+        }
+
+        if let Some(power_monitor) = &mut power_monitor {
+            match power_monitor.read() {
+                Ok(reading) => {
+                    let energy_wh = energy.record(reading.power());
+                    publish_power(&mut client, app_config.location, reading, energy_wh);
+                }
+                Err(err) => log::warn!("error reading INA219: {:}", err),
+            }
+        }
+
+        // publish read timings to correlate them with wifi activity
+        let retry_stats = climate.stats();
+        diagnostics.memory = MemoryStats::sample();
+        let diagnostics_msg = Payload::<640>::format(format_args!(
+            "{{\"location\": \"{}\", \"privacy_mode\": {}, \"dht22\": {}, \"dht22_retries\": {{\"reads\": {}, \"retries\": {}, \"failures\": {}}}, \"mhz19\": {}, \"wifi_reconnects\": {}, \"memory\": {}}}",
+            JsonStr(app_config.location),
+            client.is_local_only(),
+            diagnostics.dht22,
+            retry_stats.reads,
+            retry_stats.retries,
+            retry_stats.failures,
+            diagnostics.mhz19,
+            wifi_supervisor.reconnects(),
+            diagnostics.memory
+        ));
+        match diagnostics_msg {
+            Ok(diagnostics_msg) => {
+                log::info!("Diagnostics: {}", diagnostics_msg);
                 let publ_status = client.publish(
-                    "home/data/climate",
-                    QoS::AtLeastOnce,
+                    "home/data/diagnostics",
+                    QoS::AtMostOnce,
                     false,
-                    ambient_data_msg.as_bytes(),
+                    diagnostics_msg.as_bytes(),
                 );
-                match publ_status {
-                    Ok(_) => {}
-                    Err(err) => log::warn!("error publishing climate data: {:}", err),
-                };
+                if let Err(err) = publ_status {
+                    log::warn!("error publishing diagnostics: {:}", err);
+                }
+            }
+            Err(err) => log::warn!("error encoding diagnostics: {:}", err),
+        }
+        if stats_schedule.is_due(Instant::now()) {
+            publish_stats(&mut client, app_config.location, &stats);
+        }
+
+        #[cfg(feature = "status-server")]
+        if let Some(status_server) = &status_server {
+            status_server.update(Status {
+                measurement: Some(measurement),
+                diagnostics,
+                dht22_retries: retry_stats,
+                wifi_reconnects: wifi_supervisor.reconnects(),
+                privacy_mode: client.is_local_only(),
+                stats,
+            });
+        }
+
+        power::record_cycle();
+
+        // measure at round wall clock times so readings of several nodes line up,
+        // recomputing the wait every cycle also corrects the drift of the loop
+        let wait = if app_config.align_samples && clock.is_synced() {
+            clock.until_aligned(measurement_interval)
+        } else {
+            measurement_interval
+        };
+        #[cfg(not(feature = "console"))]
+        sleep(wait);
+        #[cfg(feature = "console")]
+        let mut next_measurement = Instant::now() + wait;
+        #[cfg(feature = "console")]
+        while let Ok(command) =
+            commands.recv_timeout(next_measurement.saturating_duration_since(Instant::now()))
+        {
+            match command {
+                Command::SelfTest => {
+                    let mut report = SelfTestReport::default();
+                    report.check(
+                        climate.sensor().name(),
+                        climate.sensor().read_temp_hum(&mut FreeRtos),
+                    );
+                    report.check(co2.name(), co2.read_ppm());
+                    if let Some(bus) = &i2c_bus {
+                        for &(name, address) in &i2c_devices {
+                            report.probe_i2c(name, &mut SharedI2c::new(bus), address);
+                        }
+                    }
+                    // initializes the display anew, it shows the next measurement as before
+                    #[cfg(feature = "display")]
+                    if let (Some(bus), Some(controller)) = (&i2c_bus, display_controller) {
+                        report.check("display", Oled::new(SharedI2c::new(bus), controller).init());
+                    }
+                    report.check(
+                        "wifi",
+                        wifi.is_connected()
+                            .map_err(anyhow::Error::from)
+                            .and_then(|conn| {
+                                conn.then_some(()).ok_or(anyhow::anyhow!("not connected"))
+                            }),
+                    );
+                    log::info!("selftest relay: skipped, the node drives no relay");
+                    let report_msg = report.to_json();
+                    println!("{}", report_msg);
+                    if let Err(err) = client.publish(
+                        "home/data/selftest",
+                        QoS::AtLeastOnce,
+                        false,
+                        report_msg.as_bytes(),
+                    ) {
+                        log::warn!("error publishing selftest report: {:}", err);
+                    }
+                }
+                Command::Privacy(local_only) => client.set_local_only(local_only),
+                Command::Config(key, value) => match config_store.set(key, &value) {
+                    Ok(()) => log::info!("{} stored, used after the next restart", key.name()),
+                    Err(err) => log::warn!("error storing {}: {:}", key.name(), err),
+                },
+                Command::ConfigReset => match config_store.reset() {
+                    Ok(()) => log::info!("Stored configuration removed"),
+                    Err(err) => log::warn!("error removing configuration: {:}", err),
+                },
+                Command::MHz19Raw(frame) => match co2.mhz19() {
+                    Some(mhz19) => match mhz19.send_raw(frame, &mut FreeRtos) {
+                        Ok(response) => println!("{:02x?}", response),
+                        Err(err) => log::warn!("error sending raw frame: {:}", err),
+                    },
+                    None => log::warn!("No MH-Z19 in use"),
+                },
+                Command::MHz19Zero => match co2.mhz19() {
+                    Some(mhz19) => match mhz19.calibrate_zero_point(&mut FreeRtos) {
+                        Ok(()) => log::info!("MH-Z19 zero point calibrated"),
+                        Err(err) => log::warn!("error calibrating zero point: {:}", err),
+                    },
+                    None => log::warn!("No MH-Z19 in use"),
+                },
+                Command::MHz19Span(ppm) => match co2.mhz19() {
+                    Some(mhz19) => match mhz19.calibrate_span(ppm, &mut FreeRtos) {
+                        Ok(()) => log::info!("MH-Z19 span calibrated to {} ppm", ppm),
+                        Err(err) => log::warn!("error calibrating span: {:}", err),
+                    },
+                    None => log::warn!("No MH-Z19 in use"),
+                },
+                Command::MHz19Trace(enabled) => match co2.mhz19() {
+                    Some(mhz19) => mhz19.set_trace(enabled),
+                    None => log::warn!("No MH-Z19 in use"),
+                },
+                Command::MHz19Abc(enable) => match co2.mhz19() {
+                    Some(mhz19) => {
+                        match mhz19.enable_auto_calibration(enable, MHZ19_TIMEOUT_MS, &mut FreeRtos)
+                        {
+                            Ok(()) => log::info!("MH-Z19 automatic baseline correction {}", enable),
+                            Err(err) => log::warn!("error setting auto calibration: {:}", err),
+                        }
+                    }
+                    None => log::warn!("No MH-Z19 in use"),
+                },
+                Command::Measure => next_measurement = Instant::now(),
+                Command::Interval(seconds) => {
+                    if let Err(err) = config_store.set(config::Key::Interval, &seconds.to_string())
+                    {
+                        log::warn!("error storing interval_s: {:}", err);
+                    }
+                    let interval = Duration::from_secs(seconds as u64);
+                    // the watchdog is set up for the interval at startup
+                    if interval > settings.measurement_interval {
+                        log::info!("Restarting for the measurement interval of {} s", seconds);
+                        spool_pending(&history, spool.as_mut());
+                        esp_idf_svc::hal::reset::restart();
+                    } else {
+                        log::info!("Measurement interval {} s", seconds);
+                        measurement_interval = interval;
+                    }
+                }
+                Command::Thresholds(warn, alert) => {
+                    log::info!("Co2 thresholds {} ppm warning, {} ppm alert", warn, alert);
+                    co2_warn_ppm = warn;
+                    co2_alert_ppm = alert;
+                }
+                Command::Reboot => {
+                    log::info!("Restarting");
+                    spool_pending(&history, spool.as_mut());
+                    esp_idf_svc::hal::reset::restart();
+                }
+                Command::Soak => {
+                    // the soak test runs until the next reboot
+                    drop(watchdog);
+                    let failure = soak::run(
+                        || climate.sensor().read_temp_hum(&mut FreeRtos),
+                        || co2.read_ppm(),
+                        || wifi.is_connected().unwrap_or(false),
+                        |report| {
+                            let publ_status = client.publish(
+                                "home/data/soak",
+                                QoS::AtMostOnce,
+                                false,
+                                report.as_bytes(),
+                            );
+                            if let Err(err) = publ_status {
+                                log::warn!("error publishing soak test report: {:}", err);
+                            }
+                        },
+                    );
+                    // the failing report is already published, restart like the reboot command
+                    log::warn!("Soak test failed: {}, restarting", failure);
+                    let result = format!("{{\"failed\": \"{}\"}}", failure);
+                    if let Err(err) =
+                        client.publish("home/data/soak", QoS::AtLeastOnce, false, result.as_bytes())
+                    {
+                        log::warn!("error publishing soak test result: {:}", err);
+                    }
+                    spool_pending(&history, spool.as_mut());
+                    // give the client time to send the result
+                    sleep(Duration::from_secs(1));
+                    esp_idf_svc::hal::reset::restart();
+                }
+                #[cfg(debug_assertions)]
+                Command::Fault(fault) => {
+                    faults::arm(fault);
+                    match fault {
+                        Fault::WifiDrop => {
+                            if let Err(err) = wifi.disconnect() {
+                                log::warn!("error dropping wifi connection: {:}", err);
+                            }
+                        }
+                        Fault::FillHeap(kilobytes) => faults::fill_heap(kilobytes),
+                        Fault::DhtChecksum | Fault::MHz19Drop => {}
+                    }
+                }
             }
-            Err(err) => log::warn!("{}", err),
         }
+    }
+}
 
-        sleep(Duration::from_millis(5 * 60 * 1000));
+/// Moves the pending measurements of the history into the spool before a
+/// commanded restart. A brownout resets the chip without running any code,
+/// the pending measurements are lost then.
+fn spool_pending(history: &Mutex<History>, spool: Option<&mut Spool>) {
+    let Some(spool) = spool else {
+        return;
+    };
+    for measurement in history.lock().unwrap().take_pending(usize::MAX) {
+        if let Err(err) = spool.push(&measurement) {
+            log::warn!("error spooling measurement: {:}", err);
+        }
     }
 }
@@ -0,0 +1,43 @@
+use esp_idf_svc::mdns::EspMdns;
+use esp_idf_svc::sys::EspError;
+
+/// Announces the node as `esp-co2-<location>.local` and, with the status
+/// server running, its http endpoints as `_http._tcp` service. The responder
+/// runs as long as the returned handle is kept.
+pub fn advertise(location: &str, status_server: bool) -> Result<EspMdns, EspError> {
+    let hostname = hostname(location);
+    let mut mdns = EspMdns::take()?;
+    mdns.set_hostname(&hostname)?;
+    mdns.set_instance_name(format!("CO2 sensor {}", location))?;
+    if status_server {
+        mdns.add_service(
+            None,
+            "_http",
+            "_tcp",
+            80,
+            &[
+                ("location", location),
+                ("firmware", env!("CARGO_PKG_VERSION")),
+                ("path", "/status"),
+            ],
+        )?;
+    }
+    log::info!("Announced as {}.local", hostname);
+    Ok(mdns)
+}
+
+/// Host names only allow letters, digits and `-`, other characters of the
+/// location are replaced, e.g. `living_room` becomes `esp-co2-living-room`
+fn hostname(location: &str) -> String {
+    let location: String = location
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("esp-co2-{}", location.trim_matches('-'))
+}
@@ -0,0 +1,135 @@
+use crate::derived;
+use core::fmt;
+
+/// Values of all sensors of one measurement cycle, a value is only meaningful
+/// if its validity flag is set
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Measurement {
+    /// unix time in seconds, none while the clock is not synchronized
+    pub timestamp: Option<u64>,
+    pub co2_ppm: i32,
+    pub temperature_c: f32,
+    pub humidity_pct: f32,
+    pub pressure_hpa: f32,
+    pub co2_valid: bool,
+    pub temperature_valid: bool,
+    pub humidity_valid: bool,
+    pub pressure_valid: bool,
+}
+
+impl Measurement {
+    /// Creates a measurement without any valid value
+    pub fn new(timestamp: Option<u64>) -> Self {
+        Self {
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    pub fn co2(&self) -> Option<i32> {
+        self.co2_valid.then_some(self.co2_ppm)
+    }
+
+    pub fn temperature(&self) -> Option<f32> {
+        self.temperature_valid.then_some(self.temperature_c)
+    }
+
+    pub fn humidity(&self) -> Option<f32> {
+        self.humidity_valid.then_some(self.humidity_pct)
+    }
+
+    /// Only measured by the BME280 and BMP280
+    pub fn pressure(&self) -> Option<f32> {
+        self.pressure_valid.then_some(self.pressure_hpa)
+    }
+
+    /// Dew point in °C derived from the temperature and humidity
+    pub fn dew_point(&self) -> Option<f32> {
+        Some(derived::dew_point(self.temperature()?, self.humidity()?))
+    }
+
+    /// Water content of the air in g/m³ derived from the temperature and humidity
+    pub fn absolute_humidity(&self) -> Option<f32> {
+        Some(derived::absolute_humidity(
+            self.temperature()?,
+            self.humidity()?,
+        ))
+    }
+
+    /// Apparent temperature in °C derived from the temperature and humidity
+    pub fn heat_index(&self) -> Option<f32> {
+        Some(derived::heat_index(self.temperature()?, self.humidity()?))
+    }
+
+    pub fn set_co2(&mut self, co2_ppm: i32) {
+        self.co2_ppm = co2_ppm;
+        self.co2_valid = true;
+    }
+
+    pub fn set_temperature(&mut self, temperature_c: f32) {
+        self.temperature_c = temperature_c;
+        self.temperature_valid = true;
+    }
+
+    pub fn set_humidity(&mut self, humidity_pct: f32) {
+        self.humidity_pct = humidity_pct;
+        self.humidity_valid = true;
+    }
+
+    pub fn set_pressure(&mut self, pressure_hpa: f32) {
+        self.pressure_hpa = pressure_hpa;
+        self.pressure_valid = true;
+    }
+
+    /// Returns true if no sensor delivered a value
+    pub fn is_empty(&self) -> bool {
+        !(self.co2_valid || self.temperature_valid || self.humidity_valid || self.pressure_valid)
+    }
+
+    /// Returns true if not all sensors delivered a value, the pressure is
+    /// optional
+    pub fn is_partial(&self) -> bool {
+        !(self.co2_valid && self.temperature_valid && self.humidity_valid)
+    }
+}
+
+/// Formats the valid values as json object without allocating, e.g.
+/// `{"co2":612,"temp":21.4,"hum":48.2,"dew":10.0,"abs_hum":9.0,"heat":20.9,"ts":1704110400}`
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        f.write_str("{")?;
+        if let Some(co2) = self.co2() {
+            write!(f, "\"co2\":{}", co2)?;
+            separator = ",";
+        }
+        if let Some(temperature) = self.temperature() {
+            write!(f, "{}\"temp\":{:.1}", separator, temperature)?;
+            separator = ",";
+        }
+        if let Some(humidity) = self.humidity() {
+            write!(f, "{}\"hum\":{:.1}", separator, humidity)?;
+            separator = ",";
+        }
+        if let Some(pressure) = self.pressure() {
+            write!(f, "{}\"pres\":{:.1}", separator, pressure)?;
+            separator = ",";
+        }
+        if let (Some(dew_point), Some(absolute_humidity), Some(heat_index)) = (
+            self.dew_point(),
+            self.absolute_humidity(),
+            self.heat_index(),
+        ) {
+            write!(
+                f,
+                "{}\"dew\":{:.1},\"abs_hum\":{:.1},\"heat\":{:.1}",
+                separator, dew_point, absolute_humidity, heat_index
+            )?;
+            separator = ",";
+        }
+        if let Some(timestamp) = self.timestamp {
+            write!(f, "{}\"ts\":{}", separator, timestamp)?;
+        }
+        f.write_str("}")
+    }
+}
@@ -1,10 +1,28 @@
+use crate::sensors::Co2Sensor;
 use core::fmt;
-use embedded_io::{Read, Write};
+use core::time::Duration;
+use embedded_hal::delay::DelayUs;
+use embedded_io::{Read, ReadReady, Write};
+
+/// Interval to check for the response while reading with a timeout
+const POLL_INTERVAL_MS: u32 = 10;
+/// The sensor answers within a few ms, a missing sensor must not block the caller
+const READ_TIMEOUT_MS: u32 = 1000;
+/// Wait for the acknowledgement of a setting, it follows within a few ms
+const ACK_TIMEOUT_MS: u32 = 100;
 
 #[derive(Debug)]
 pub enum MHz19Error<HE> {
     /// received and calculated checksums do not match
     Checksum(u8, u8),
+    /// the sensor did not answer within the timeout
+    Timeout,
+    /// the span calibration requires a concentration of at least 1000 ppm
+    InvalidSpan(u16),
+    /// the response belongs to another command than the one sent
+    UnexpectedResponse(u8),
+    /// the sensor acknowledged a setting but reports a different one
+    SettingNotApplied,
     /// Error of underlying IO
     HalError(HE),
 }
@@ -20,6 +38,10 @@ impl<HE: fmt::Debug> fmt::Display for MHz19Error<HE> {
         use MHz19Error::*;
         match self {
             Checksum(exp, act) => write!(f, "Checksum error: 0x{:x} vs 0x{:x}", exp, act),
+            Timeout => write!(f, "Timeout waiting for response"),
+            InvalidSpan(ppm) => write!(f, "Span of {} ppm below 1000 ppm", ppm),
+            UnexpectedResponse(cmd) => write!(f, "Unexpected response to command 0x{:x}", cmd),
+            SettingNotApplied => write!(f, "Setting not applied by the sensor"),
             HalError(err) => write!(f, "HAL error: {:?}", err),
         }
     }
@@ -28,47 +50,671 @@ impl<HE: fmt::Debug> fmt::Display for MHz19Error<HE> {
 #[cfg(feature = "std")]
 impl<HE: fmt::Debug> std::error::Error for MHz19Error<HE> {}
 
-pub struct MHz19<HE, U: Read<Error = HE> + Write<Error = HE>> {
+/// Measurement range of the MH-Z19
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectionRange {
+    Ppm2000,
+    Ppm5000,
+    Ppm10000,
+}
+
+impl DetectionRange {
+    pub fn from_ppm(ppm: u16) -> Option<Self> {
+        match ppm {
+            2000 => Some(DetectionRange::Ppm2000),
+            5000 => Some(DetectionRange::Ppm5000),
+            10000 => Some(DetectionRange::Ppm10000),
+            _ => None,
+        }
+    }
+
+    pub fn ppm(&self) -> u16 {
+        match self {
+            DetectionRange::Ppm2000 => 2000,
+            DetectionRange::Ppm5000 => 5000,
+            DetectionRange::Ppm10000 => 10000,
+        }
+    }
+}
+
+/// Time the MH-Z19 needs after power on until its readings are valid
+pub const WARMUP: Duration = Duration::from_secs(3 * 60);
+
+/// Tells whether the sensor finished its warm-up, earlier readings are
+/// provisional
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WarmupTracker {
+    warmup: Duration,
+}
+
+impl WarmupTracker {
+    /// Creates the tracker for a sensor that was just powered on
+    pub fn new() -> Self {
+        Self { warmup: WARMUP }
+    }
+
+    /// Creates the tracker for a sensor that stayed powered, e.g. across a
+    /// software reset of the controller
+    pub fn warmed_up() -> Self {
+        Self {
+            warmup: Duration::ZERO,
+        }
+    }
+
+    /// Returns true once the sensor is powered for the warm-up time
+    pub fn is_ready(&self, since_power_on: Duration) -> bool {
+        since_power_on >= self.warmup
+    }
+}
+
+impl Default for WarmupTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Command bytes of the MH-Z19 protocol
+const CMD_READ_CO2: u8 = 0x86;
+const CMD_ZERO_POINT: u8 = 0x87;
+const CMD_SPAN: u8 = 0x88;
+const CMD_AUTO_CALIBRATION: u8 = 0x79;
+const CMD_AUTO_CALIBRATION_STATUS: u8 = 0x7D;
+const CMD_DETECTION_RANGE: u8 = 0x99;
+
+/// Command or response of the MH-Z19 protocol: the start byte `0xff`, the
+/// sensor number in commands or the command byte in responses, six data bytes
+/// and the checksum, e.g. `ff 01 86 00 00 00 00 00 79` to read the co2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame([u8; 9]);
+
+impl Frame {
+    /// Builds the command with the data bytes 3 to 7 for sensor 1, the only
+    /// one the MH-Z19 listens to
+    pub fn command(cmd: u8, data: [u8; 5]) -> Self {
+        let [b3, b4, b5, b6, b7] = data;
+        Self::with_checksum([0xFF, 0x1, cmd, b3, b4, b5, b6, b7, 0])
+    }
+
+    /// Takes the bytes as they are, e.g. of a received response
+    pub fn from_bytes(bytes: [u8; 9]) -> Self {
+        Self(bytes)
+    }
+
+    /// Takes the bytes with the checksum byte recalculated
+    pub fn with_checksum(mut bytes: [u8; 9]) -> Self {
+        bytes[8] = Self::checksum_of(&bytes);
+        Self(bytes)
+    }
+
+    pub fn bytes(&self) -> &[u8; 9] {
+        &self.0
+    }
+
+    /// Returns the command a response answers
+    pub fn response_to(&self) -> u8 {
+        self.0[1]
+    }
+
+    /// Returns the big endian value of the bytes `index` and `index + 1`
+    pub fn u16_at(&self, index: usize) -> u16 {
+        u16::from_be_bytes([self.0[index], self.0[index + 1]])
+    }
+
+    /// Checks the checksum of a received frame
+    pub fn verify<HE>(&self) -> Result<(), MHz19Error<HE>> {
+        let checksum = Self::checksum_of(&self.0);
+        if checksum != self.0[8] {
+            return Err(MHz19Error::Checksum(checksum, self.0[8]));
+        }
+        Ok(())
+    }
+
+    /// Checks the checksum and that the response answers the given command
+    pub fn verify_response<HE>(&self, cmd: u8) -> Result<(), MHz19Error<HE>> {
+        self.verify()?;
+        if self.response_to() != cmd {
+            return Err(MHz19Error::UnexpectedResponse(self.response_to()));
+        }
+        Ok(())
+    }
+
+    /// The checksum is the two's complement of the sum of the bytes 1 to 7,
+    /// e.g. 0x79 for the read command `ff 01 86 00 00 00 00 00`
+    #[must_use]
+    fn checksum_of(bytes: &[u8; 9]) -> u8 {
+        bytes[1..=7]
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+            .wrapping_neg()
+    }
+}
+
+/// Sensor of the MH-Z family on the uart, the MH-Z14/MH-Z14A share the
+/// protocol of the MH-Z19 but only know a part of its commands
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SensorModel {
+    #[default]
+    MHz19,
+    MHz14,
+}
+
+impl SensorModel {
+    /// Only the MH-Z19 sends its temperature in the gas concentration response
+    fn reports_temperature(self) -> bool {
+        self == SensorModel::MHz19
+    }
+
+    /// The status of the automatic baseline correction can only be read back
+    /// from the MH-Z19B/C
+    fn reports_auto_calibration(self) -> bool {
+        self == SensorModel::MHz19
+    }
+}
+
+/// Full response of the gas concentration command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Co2Readout {
+    pub co2_ppm: i32,
+    /// temperature of the sensor, only accurate to a few degrees, none for
+    /// the MH-Z14
+    pub sensor_temp_c: Option<i32>,
+    /// undocumented status byte
+    pub status: u8,
+}
+
+pub struct MHz19<HE, U: Read<Error = HE> + ReadReady<Error = HE> + Write<Error = HE>> {
     uart: U,
+    model: SensorModel,
+    range: Option<DetectionRange>,
+    trace: bool,
 }
 
-impl<HE, U: Read<Error = HE> + Write<Error = HE>> MHz19<HE, U> {
+impl<HE, U: Read<Error = HE> + ReadReady<Error = HE> + Write<Error = HE>> MHz19<HE, U> {
+    /// Creates the driver for a MH-Z19
     pub fn new(uart: U) -> Self {
-        Self { uart }
+        Self::with_model(uart, SensorModel::MHz19)
     }
 
-    fn calculate_checksum(data: &[u8]) -> u8 {
-        let mut checksum = 0;
-        for i in 1..=7 {
-            checksum += data[i] as i16;
+    pub fn with_model(uart: U, model: SensorModel) -> Self {
+        Self {
+            uart,
+            model,
+            range: None,
+            trace: false,
         }
-        checksum = 0xff - checksum;
-        (checksum + 1) as u8
     }
 
-    pub fn read_co2(&mut self) -> Result<i32, MHz19Error<HE>> {
-        let read_cmd = [0xFF, 0x1, 0x86, 0, 0, 0, 0, 0, 0x79];
-        self.uart.write(&read_cmd)?;
+    /// Logs every frame sent and received at info level, e.g. to debug the
+    /// wiring in the field without a build with the `trace` feature
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
 
-        let mut response: [u8; 9] = [0; 9];
-        self.uart.read(&mut response)?;
+    fn trace(&self, direction: &str, frame: &[u8]) {
+        if self.trace {
+            log::info!("MH-Z19 {} {:02x?}", direction, frame);
+        } else {
+            #[cfg(feature = "trace")]
+            log::trace!("MH-Z19 {} {:02x?}", direction, frame);
+        }
+    }
 
-        let checksum = Self::calculate_checksum(&response);
-        if checksum != response[8] {
-            return Err(MHz19Error::Checksum(checksum, response[8]));
+    /// Discards bytes left in the receive buffer, e.g. the late response to a
+    /// command that timed out, which would be taken as the next response
+    fn drain(&mut self) -> Result<(), MHz19Error<HE>> {
+        let mut stale = [0u8; 9];
+        let mut discarded = 0;
+        // read_ready only promises a single byte, reading more could wait for
+        // bytes that never come. The uart receives slower than this loop reads,
+        // so it ends even on a line picking up noise.
+        while self.uart.read_ready()? {
+            let index = discarded % stale.len();
+            if self.uart.read(&mut stale[index..=index])? == 0 {
+                break;
+            }
+            discarded += 1;
+            if index + 1 == stale.len() {
+                self.trace("stale", &stale);
+            }
+        }
+        if discarded % stale.len() > 0 {
+            self.trace("stale", &stale[..discarded % stale.len()]);
+        }
+        if discarded > 0 {
+            log::warn!("MH-Z19 discarded {} stale bytes", discarded);
         }
+        Ok(())
+    }
 
-        Ok(((response[2] as i32) << 8) + response[3] as i32)
+    fn send(&mut self, frame: &Frame) -> Result<(), MHz19Error<HE>> {
+        self.drain()?;
+        self.trace("TX", frame.bytes());
+        self.uart.write(frame.bytes())?;
+        Ok(())
+    }
+
+    /// Sends the command and returns the verified response
+    fn request(
+        &mut self,
+        cmd: u8,
+        data: [u8; 5],
+        timeout_ms: u32,
+        delay: &mut impl DelayUs,
+    ) -> Result<Frame, MHz19Error<HE>> {
+        self.send(&Frame::command(cmd, data))?;
+        let response = self.receive_timeout(timeout_ms, delay)?;
+        response.verify_response(cmd)?;
+        Ok(response)
     }
 
-    pub fn enable_auto_calibration(&mut self, enable: bool) -> Result<(), MHz19Error<HE>> {
-        let mut cmd = [0xFF, 0x1, 0x79, 0, 0, 0, 0, 0, 0];
-        if enable {
-            cmd[3] = 0xA0;
+    /// Sends a calibration command and discards its acknowledgement, so it is
+    /// not taken as the next response. The datasheet gives no answer for the
+    /// calibrations, but some sensors send one, a missing one is no error.
+    fn calibrate(
+        &mut self,
+        cmd: u8,
+        data: [u8; 5],
+        delay: &mut impl DelayUs,
+    ) -> Result<(), MHz19Error<HE>> {
+        match self.request(cmd, data, ACK_TIMEOUT_MS, delay) {
+            Ok(_) | Err(MHz19Error::Timeout) => Ok(()),
+            Err(err) => Err(err),
         }
-        cmd[8] = Self::calculate_checksum(&cmd);
-        self.uart.write(&cmd)?;
+    }
+
+    /// Reads the co2 concentration, gives up with `MHz19Error::Timeout` if the
+    /// sensor does not answer within a second
+    pub fn read_co2(&mut self, delay: &mut impl DelayUs) -> Result<i32, MHz19Error<HE>> {
+        self.read_co2_timeout(READ_TIMEOUT_MS, delay)
+    }
+
+    /// Reads the co2 concentration together with the sensor temperature and status
+    pub fn read_measurement(
+        &mut self,
+        delay: &mut impl DelayUs,
+    ) -> Result<Co2Readout, MHz19Error<HE>> {
+        let response = self.request(CMD_READ_CO2, [0; 5], READ_TIMEOUT_MS, delay)?;
+        Ok(self.parse_measurement(&response))
+    }
+
+    fn parse_measurement(&self, response: &Frame) -> Co2Readout {
+        let co2 = response.u16_at(2) as i32;
+        Co2Readout {
+            // readings beyond the configured range are not reliable
+            co2_ppm: match self.range {
+                Some(range) => co2.min(range.ppm() as i32),
+                None => co2,
+            },
+            // the temperature is sent with an offset of 40
+            sensor_temp_c: self
+                .model
+                .reports_temperature()
+                .then(|| response.bytes()[4] as i32 - 40),
+            status: response.bytes()[5],
+        }
+    }
+
+    /// Sends an arbitrary frame with corrected checksum and returns the raw
+    /// response, `MHz19Error::Timeout` if there is none within a second
+    pub fn send_raw(
+        &mut self,
+        frame: [u8; 9],
+        delay: &mut impl DelayUs,
+    ) -> Result<[u8; 9], MHz19Error<HE>> {
+        self.send(&Frame::with_checksum(frame))?;
+        Ok(*self.receive_timeout(READ_TIMEOUT_MS, delay)?.bytes())
+    }
+
+    /// Calibrates the zero point, the current concentration becomes 400 ppm.
+    /// The sensor needs to be in fresh air for at least 20 minutes before.
+    pub fn calibrate_zero_point(&mut self, delay: &mut impl DelayUs) -> Result<(), MHz19Error<HE>> {
+        self.calibrate(CMD_ZERO_POINT, [0; 5], delay)
+    }
+
+    /// Calibrates the span to the given concentration, the zero point has to be
+    /// calibrated before. The datasheet requires a span of at least 1000 ppm.
+    pub fn calibrate_span(
+        &mut self,
+        ppm: u16,
+        delay: &mut impl DelayUs,
+    ) -> Result<(), MHz19Error<HE>> {
+        if ppm < 1000 {
+            return Err(MHz19Error::InvalidSpan(ppm));
+        }
+        let [high, low] = ppm.to_be_bytes();
+        self.calibrate(CMD_SPAN, [high, low, 0, 0, 0], delay)
+    }
+
+    /// Sets the measurement range, once the sensor acknowledged it readings are
+    /// clamped to it. Returns `MHz19Error::Timeout` without an acknowledgement.
+    pub fn set_detection_range(
+        &mut self,
+        range: DetectionRange,
+        delay: &mut impl DelayUs,
+    ) -> Result<(), MHz19Error<HE>> {
+        let [high, low] = range.ppm().to_be_bytes();
+        self.request(
+            CMD_DETECTION_RANGE,
+            [0, 0, 0, high, low],
+            ACK_TIMEOUT_MS,
+            delay,
+        )?;
+        self.range = Some(range);
 
         Ok(())
     }
+
+    /// Reads the co2 concentration like `read_co2`, but with the given timeout
+    pub fn read_co2_timeout(
+        &mut self,
+        timeout_ms: u32,
+        delay: &mut impl DelayUs,
+    ) -> Result<i32, MHz19Error<HE>> {
+        let response = self.request(CMD_READ_CO2, [0; 5], timeout_ms, delay)?;
+        Ok(self.parse_measurement(&response).co2_ppm)
+    }
+
+    /// Enables or disables the automatic baseline correction. The setting is
+    /// read back if the sensor supports it (MH-Z19B/C, not the MH-Z14) and
+    /// `MHz19Error::SettingNotApplied` returned if it did not take.
+    pub fn enable_auto_calibration(
+        &mut self,
+        enable: bool,
+        timeout_ms: u32,
+        delay: &mut impl DelayUs,
+    ) -> Result<(), MHz19Error<HE>> {
+        let abc = if enable { 0xA0 } else { 0 };
+        self.request(CMD_AUTO_CALIBRATION, [abc, 0, 0, 0, 0], timeout_ms, delay)?;
+
+        if !self.model.reports_auto_calibration() {
+            return Ok(());
+        }
+        // older sensors do not know the status command
+        match self.get_auto_calibration_status(timeout_ms, delay) {
+            Ok(enabled) if enabled != enable => Err(MHz19Error::SettingNotApplied),
+            Ok(_) | Err(MHz19Error::Timeout) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns whether the automatic baseline correction is enabled, only
+    /// supported by the MH-Z19B/C
+    pub fn get_auto_calibration_status(
+        &mut self,
+        timeout_ms: u32,
+        delay: &mut impl DelayUs,
+    ) -> Result<bool, MHz19Error<HE>> {
+        let response = self.request(CMD_AUTO_CALIBRATION_STATUS, [0; 5], timeout_ms, delay)?;
+        Ok(response.bytes()[7] != 0)
+    }
+
+    fn receive_timeout(
+        &mut self,
+        timeout_ms: u32,
+        delay: &mut impl DelayUs,
+    ) -> Result<Frame, MHz19Error<HE>> {
+        let mut response: [u8; 9] = [0; 9];
+        let mut len = 0;
+        let mut waited = 0;
+        while len < response.len() {
+            let read = if self.uart.read_ready()? {
+                self.uart.read(&mut response[len..])?
+            } else {
+                0
+            };
+            // the frame arrives in parts, the timeout counts while no byte is waiting
+            if read > 0 {
+                len += read;
+            } else if waited >= timeout_ms {
+                self.trace("RX partial", &response[..len]);
+                return Err(MHz19Error::Timeout);
+            } else {
+                delay.delay_ms(POLL_INTERVAL_MS);
+                waited += POLL_INTERVAL_MS;
+            }
+        }
+        self.trace("RX", &response);
+        Ok(Frame::from_bytes(response))
+    }
+}
+
+impl<HE, U: Read<Error = HE> + ReadReady<Error = HE> + Write<Error = HE>> Co2Sensor
+    for MHz19<HE, U>
+{
+    type Error = MHz19Error<HE>;
+
+    fn read_ppm(&mut self, mut delay: &mut dyn DelayUs) -> Result<i32, MHz19Error<HE>> {
+        self.read_co2_timeout(READ_TIMEOUT_MS, &mut delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use std::collections::VecDeque;
+
+    /// Uart that answers every write with the next queued reply, delivered in
+    /// parts of `chunk` bytes
+    struct MockUart {
+        rx: VecDeque<u8>,
+        replies: VecDeque<Vec<u8>>,
+        chunk: usize,
+        tx: Vec<u8>,
+    }
+
+    impl MockUart {
+        fn new(replies: &[&[u8]]) -> Self {
+            Self {
+                rx: VecDeque::new(),
+                replies: replies.iter().map(|reply| reply.to_vec()).collect(),
+                chunk: 9,
+                tx: Vec::new(),
+            }
+        }
+    }
+
+    impl embedded_io::ErrorType for MockUart {
+        type Error = Infallible;
+    }
+
+    impl Read for MockUart {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            let len = buf.len().min(self.chunk).min(self.rx.len());
+            for byte in &mut buf[..len] {
+                *byte = self.rx.pop_front().unwrap();
+            }
+            Ok(len)
+        }
+    }
+
+    impl ReadReady for MockUart {
+        fn read_ready(&mut self) -> Result<bool, Infallible> {
+            Ok(!self.rx.is_empty())
+        }
+    }
+
+    impl Write for MockUart {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.tx.extend_from_slice(buf);
+            if let Some(reply) = self.replies.pop_front() {
+                self.rx.extend(reply);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    struct NoDelay;
+
+    impl DelayUs for NoDelay {
+        fn delay_us(&mut self, _us: u32) {}
+    }
+
+    // response to the read command from the datasheet, 608 ppm at 31 °C
+    const READ_RESPONSE: [u8; 9] = [0xFF, 0x86, 0x02, 0x60, 0x47, 0x00, 0x00, 0x00, 0xD1];
+    // acknowledgement of the detection range command
+    const RANGE_ACK: [u8; 9] = [0xFF, 0x99, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x66];
+
+    #[test]
+    fn commands_match_the_datasheet() {
+        let frames = [
+            (Frame::command(CMD_READ_CO2, [0; 5]), 0x79),
+            (Frame::command(CMD_ZERO_POINT, [0; 5]), 0x78),
+            (Frame::command(CMD_SPAN, [0x07, 0xD0, 0, 0, 0]), 0xA0),
+            (
+                Frame::command(CMD_AUTO_CALIBRATION, [0xA0, 0, 0, 0, 0]),
+                0xE6,
+            ),
+            (Frame::command(CMD_AUTO_CALIBRATION, [0; 5]), 0x86),
+            (
+                Frame::command(CMD_DETECTION_RANGE, [0, 0, 0, 0x13, 0x88]),
+                0xCB,
+            ),
+        ];
+        for (frame, checksum) in frames {
+            assert_eq!(frame.bytes()[8], checksum, "{:02x?}", frame);
+            assert!(frame.verify::<Infallible>().is_ok());
+        }
+    }
+
+    #[test]
+    fn checksum_wraps_for_every_byte_value() {
+        for position in 1..=7 {
+            for value in 0..=u8::MAX {
+                let mut bytes = [0xFF; 9];
+                bytes[position] = value;
+                let frame = Frame::with_checksum(bytes);
+                let sum = frame.bytes()[1..]
+                    .iter()
+                    .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+                assert_eq!(sum, 0);
+                assert!(frame.verify::<Infallible>().is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn verify_rejects_corrupted_frames() {
+        let mut bytes = READ_RESPONSE;
+        bytes[3] ^= 0x01;
+        assert!(matches!(
+            Frame::from_bytes(bytes).verify::<Infallible>(),
+            Err(MHz19Error::Checksum(0xD0, 0xD1))
+        ));
+        assert!(matches!(
+            Frame::from_bytes(READ_RESPONSE).verify_response::<Infallible>(CMD_SPAN),
+            Err(MHz19Error::UnexpectedResponse(CMD_READ_CO2))
+        ));
+    }
+
+    #[test]
+    fn reads_co2_from_a_frame_in_parts() {
+        let mut uart = MockUart::new(&[&READ_RESPONSE]);
+        uart.chunk = 2;
+        let mut sensor = MHz19::new(uart);
+        let readout = sensor.read_measurement(&mut NoDelay).unwrap();
+        assert_eq!(
+            readout,
+            Co2Readout {
+                co2_ppm: 608,
+                sensor_temp_c: Some(31),
+                status: 0,
+            }
+        );
+        assert_eq!(
+            sensor.uart.tx,
+            [0xFF, 0x01, 0x86, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79]
+        );
+    }
+
+    #[test]
+    fn clamps_readings_to_the_detection_range() {
+        let response = Frame::with_checksum([0xFF, 0x86, 0x0B, 0xB8, 0x47, 0, 0, 0, 0]);
+        let mut sensor = MHz19::with_model(
+            MockUart::new(&[&RANGE_ACK, response.bytes()]),
+            SensorModel::MHz14,
+        );
+        sensor
+            .set_detection_range(DetectionRange::Ppm2000, &mut NoDelay)
+            .unwrap();
+        let readout = sensor.read_measurement(&mut NoDelay).unwrap();
+        assert_eq!(readout.co2_ppm, 2000);
+        assert_eq!(readout.sensor_temp_c, None);
+    }
+
+    #[test]
+    fn keeps_the_range_without_an_acknowledgement() {
+        let response = Frame::with_checksum([0xFF, 0x86, 0x0B, 0xB8, 0x47, 0, 0, 0, 0]);
+        let mut sensor = MHz19::new(MockUart::new(&[&[], response.bytes()]));
+        assert!(matches!(
+            sensor.set_detection_range(DetectionRange::Ppm2000, &mut NoDelay),
+            Err(MHz19Error::Timeout)
+        ));
+        assert_eq!(sensor.read_co2(&mut NoDelay).unwrap(), 3000);
+    }
+
+    #[test]
+    fn discards_the_acknowledgement_of_a_calibration() {
+        let ack = Frame::with_checksum([0xFF, CMD_ZERO_POINT, 0, 0, 0, 0, 0, 0, 0]);
+        let mut sensor = MHz19::new(MockUart::new(&[ack.bytes(), &READ_RESPONSE]));
+        sensor.calibrate_zero_point(&mut NoDelay).unwrap();
+        assert!(sensor.uart.rx.is_empty());
+        assert_eq!(sensor.read_co2(&mut NoDelay).unwrap(), 608);
+        // the datasheet gives no answer to the calibrations
+        let mut sensor = MHz19::new(MockUart::new(&[]));
+        sensor.calibrate_span(2000, &mut NoDelay).unwrap();
+    }
+
+    #[test]
+    fn discards_stale_bytes_before_a_command() {
+        let mut uart = MockUart::new(&[&READ_RESPONSE]);
+        uart.rx.extend([0xFF, 0x86, 0x01]);
+        let mut sensor = MHz19::new(uart);
+        assert_eq!(sensor.read_co2(&mut NoDelay).unwrap(), 608);
+    }
+
+    #[test]
+    fn times_out_without_a_complete_frame() {
+        let mut sensor = MHz19::new(MockUart::new(&[]));
+        assert!(matches!(
+            sensor.read_co2(&mut NoDelay),
+            Err(MHz19Error::Timeout)
+        ));
+        let mut sensor = MHz19::new(MockUart::new(&[&READ_RESPONSE[..5]]));
+        assert!(matches!(
+            sensor.read_co2(&mut NoDelay),
+            Err(MHz19Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn rejects_corrupted_and_unexpected_responses() {
+        let mut corrupted = READ_RESPONSE;
+        corrupted[2] = 0x03;
+        let mut sensor = MHz19::new(MockUart::new(&[&corrupted]));
+        assert!(matches!(
+            sensor.read_co2(&mut NoDelay),
+            Err(MHz19Error::Checksum(..))
+        ));
+        let other = Frame::with_checksum([0xFF, 0x79, 0, 0, 0, 0, 0, 0, 0]);
+        let mut sensor = MHz19::new(MockUart::new(&[other.bytes()]));
+        assert!(matches!(
+            sensor.read_co2(&mut NoDelay),
+            Err(MHz19Error::UnexpectedResponse(CMD_AUTO_CALIBRATION))
+        ));
+    }
+
+    #[test]
+    fn rejects_a_span_below_1000_ppm() {
+        let mut sensor = MHz19::new(MockUart::new(&[]));
+        assert!(matches!(
+            sensor.calibrate_span(999, &mut NoDelay),
+            Err(MHz19Error::InvalidSpan(999))
+        ));
+        assert!(sensor.uart.tx.is_empty());
+    }
 }
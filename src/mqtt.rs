@@ -0,0 +1,191 @@
+use crate::tls::ServerVerification;
+use embedded_svc::mqtt::client::{Event, QoS};
+use esp_idf_svc::mqtt::client::{EspMqttClient, MqttClientConfiguration};
+use esp_idf_svc::sys::EspError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Time between two connection attempts after the broker dropped the connection
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Topic remote commands are received on, the payload of every message is
+/// handed to the handler
+pub struct CommandTopic {
+    pub topic: String,
+    pub handler: Box<dyn FnMut(&str) + Send>,
+}
+
+/// Publishes messages to the mqtt broker unless the node runs in local-only
+/// mode. Clones share the client and the mode, e.g. with the mqtt output.
+#[derive(Clone)]
+pub struct MqttPublisher {
+    client: Arc<Mutex<EspMqttClient<'static>>>,
+    connected: Arc<AtomicBool>,
+    local_only: Arc<AtomicBool>,
+}
+
+impl MqttPublisher {
+    /// Connects to the broker, the client reconnects on its own whenever the
+    /// broker drops the connection. With `tls` the connection is encrypted and
+    /// the broker is verified as given. The command topic is subscribed after
+    /// every connect.
+    #[cfg_attr(feature = "mqtt-mtls", allow(unused_variables))]
+    pub fn connect(
+        host: &str,
+        user: &str,
+        pass: &str,
+        tls: Option<ServerVerification>,
+        commands: Option<CommandTopic>,
+        local_only: bool,
+    ) -> anyhow::Result<Self> {
+        let mut mqtt_config = MqttClientConfiguration {
+            reconnect_timeout: Some(RECONNECT_TIMEOUT),
+            ..Default::default()
+        };
+        // with mutual TLS the client certificate replaces user and password
+        #[cfg(feature = "mqtt-mtls")]
+        let broker_url = {
+            crate::mqtt_tls::configure(&mut mqtt_config);
+            format!("mqtts://{}", host)
+        };
+        #[cfg(not(feature = "mqtt-mtls"))]
+        let broker_url = {
+            mqtt_config.username = (!user.is_empty()).then_some(user);
+            mqtt_config.password = (!pass.is_empty()).then_some(pass);
+            match tls {
+                Some(verification) => {
+                    verification.configure_mqtt(&mut mqtt_config);
+                    format!("mqtts://{}", host)
+                }
+                None => format!("mqtt://{}", host),
+            }
+        };
+
+        let connected = Arc::new(AtomicBool::new(false));
+        let state = connected.clone();
+        let (command_topic, mut handler) = match commands {
+            Some(CommandTopic { topic, handler }) => (Some(topic), Some(handler)),
+            None => (None, None),
+        };
+        let received_topic = command_topic.clone();
+        let (subscribe_tx, subscribe_rx) = mpsc::channel();
+        let client = EspMqttClient::new(&broker_url, &mqtt_config, move |event| match event {
+            Ok(Event::Connected(_)) => {
+                log::info!("Connected to MQTT broker");
+                state.store(true, Ordering::Relaxed);
+                let _ = subscribe_tx.send(());
+            }
+            Ok(Event::Received(message)) => {
+                // later chunks of long messages come without topic
+                let handler = handler
+                    .as_mut()
+                    .filter(|_| message.topic() == received_topic.as_deref());
+                if let Some(handler) = handler {
+                    match core::str::from_utf8(message.data()) {
+                        Ok(payload) => handler(payload.trim()),
+                        Err(_) => log::warn!("Ignoring command that is no text"),
+                    }
+                }
+            }
+            Ok(Event::Disconnected) => {
+                log::warn!("MQTT broker dropped the connection, reconnecting");
+                state.store(false, Ordering::Relaxed);
+            }
+            Err(err) => log::warn!("MQTT error: {:}", err),
+            _ => {}
+        })?;
+
+        let client = Arc::new(Mutex::new(client));
+        // the client can not subscribe from within its own event handler
+        if let Some(topic) = command_topic {
+            let subscriber = client.clone();
+            thread::Builder::new()
+                .name("mqtt-cmd".into())
+                .stack_size(4096)
+                .spawn(move || {
+                    while subscribe_rx.recv().is_ok() {
+                        let subscribed = subscriber
+                            .lock()
+                            .unwrap()
+                            .subscribe(&topic, QoS::AtLeastOnce);
+                        match subscribed {
+                            Ok(_) => log::info!("Subscribed to {}", topic),
+                            Err(err) => log::warn!("error subscribing to {}: {:}", topic, err),
+                        }
+                    }
+                })?;
+        }
+
+        Ok(Self {
+            client,
+            connected,
+            local_only: Arc::new(AtomicBool::new(local_only)),
+        })
+    }
+
+    /// Returns true while the connection to the broker is established
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if no data leaves the node
+    pub fn is_local_only(&self) -> bool {
+        self.local_only.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the local-only privacy mode
+    #[cfg(feature = "console")]
+    pub fn set_local_only(&mut self, local_only: bool) {
+        log::info!(
+            "Privacy mode {}",
+            if local_only {
+                "on, publishing stopped"
+            } else {
+                "off"
+            }
+        );
+        self.local_only.store(local_only, Ordering::Relaxed);
+    }
+
+    /// Publishes a message, the message is dropped in local-only mode and
+    /// while the broker is unreachable. Queueing them would fill the outbox
+    /// during long outages, the measurements are replayed from the history.
+    pub fn publish(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+    ) -> Result<(), EspError> {
+        if self.is_local_only() {
+            return Ok(());
+        }
+        if self.is_connected() {
+            self.client
+                .lock()
+                .unwrap()
+                .publish(topic, qos, retain, payload)?;
+        }
+        Ok(())
+    }
+
+    /// Queues a message until the broker is connected, the message is dropped
+    /// in local-only mode
+    pub fn enqueue(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+    ) -> Result<(), EspError> {
+        if !self.is_local_only() {
+            self.client
+                .lock()
+                .unwrap()
+                .enqueue(topic, qos, retain, payload)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+use esp_idf_svc::mqtt::client::MqttClientConfiguration;
+use esp_idf_svc::tls::X509;
+
+// the certificates of the device are embedded at build time, esp-idf expects
+// PEM data terminated by a nul character
+const CA_CERT: &str = concat!(include_str!("../certs/ca.crt"), "\0");
+const CLIENT_CERT: &str = concat!(include_str!("../certs/client.crt"), "\0");
+const CLIENT_KEY: &str = concat!(include_str!("../certs/client.key"), "\0");
+
+/// Configures mutual TLS authentication with the embedded device certificate
+pub fn configure(mqtt_config: &mut MqttClientConfiguration) {
+    mqtt_config.server_certificate = Some(X509::pem_until_nul(CA_CERT.as_bytes()));
+    mqtt_config.client_certificate = Some(X509::pem_until_nul(CLIENT_CERT.as_bytes()));
+    mqtt_config.private_key = Some(X509::pem_until_nul(CLIENT_KEY.as_bytes()));
+}
@@ -0,0 +1,126 @@
+use crate::derived::dew_point;
+use anyhow::{bail, Result};
+use embedded_svc::http::client::Client;
+use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
+use esp_idf_svc::sys;
+use std::time::{Duration, Instant};
+
+/// Wait before fetching again after a failed fetch, doubled after every
+/// further failure up to the regular interval
+const MIN_RETRY_WAIT: Duration = Duration::from_secs(2 * 60);
+
+/// Current outdoor weather at the configured location
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weather {
+    pub temperature: f32,
+    pub humidity: f32,
+}
+
+impl Weather {
+    pub fn dew_point(&self) -> f32 {
+        dew_point(self.temperature, self.humidity)
+    }
+}
+
+/// The latest outdoor weather, refreshed at the interval. An unreachable api
+/// is not asked every cycle, failed fetches are retried with a growing wait.
+pub struct WeatherCache {
+    interval: Duration,
+    latest: Option<Weather>,
+    next_fetch: Option<Instant>,
+    retry_wait: Duration,
+}
+
+impl WeatherCache {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            latest: None,
+            next_fetch: None,
+            retry_wait: MIN_RETRY_WAIT,
+        }
+    }
+
+    /// Returns true if the weather is to be fetched again
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.next_fetch.map_or(true, |next_fetch| now >= next_fetch)
+    }
+
+    /// Keeps the fetched weather and schedules the next fetch
+    pub fn update(&mut self, fetched: Result<Weather>, now: Instant) {
+        match fetched {
+            Ok(weather) => {
+                self.latest = Some(weather);
+                self.retry_wait = MIN_RETRY_WAIT;
+                self.next_fetch = Some(now + self.interval);
+            }
+            Err(err) => {
+                log::warn!(
+                    "error fetching outdoor weather, retrying in {} s: {:}",
+                    self.retry_wait.as_secs(),
+                    err
+                );
+                self.next_fetch = Some(now + self.retry_wait);
+                self.retry_wait = (self.retry_wait * 2).min(self.interval.max(MIN_RETRY_WAIT));
+            }
+        }
+    }
+
+    pub fn latest(&self) -> Option<Weather> {
+        self.latest
+    }
+}
+
+/// Fetches the current weather from the Open-Meteo API
+pub fn fetch(latitude: &str, longitude: &str) -> Result<Weather> {
+    let url = format!(
+        "https://api.open-meteo.com/v1/forecast?latitude={}&longitude={}&current=temperature_2m,relative_humidity_2m",
+        latitude, longitude
+    );
+    let connection = EspHttpConnection::new(&Configuration {
+        crt_bundle_attach: Some(sys::esp_crt_bundle_attach),
+        ..Default::default()
+    })?;
+    let mut client = Client::wrap(connection);
+    let mut response = client.get(&url)?.submit()?;
+    if response.status() != 200 {
+        bail!("weather api responded with status {}", response.status());
+    }
+
+    // the response is a few hundred bytes
+    let mut body = [0u8; 1024];
+    let mut len = 0;
+    while len < body.len() {
+        match response.read(&mut body[len..])? {
+            0 => break,
+            read => len += read,
+        }
+    }
+    match parse(core::str::from_utf8(&body[..len])?) {
+        Some(weather) => Ok(weather),
+        None => bail!("unexpected weather api response"),
+    }
+}
+
+/// Picks the current values out of the json response, e.g.
+/// `{.., "current": {"time": "2024-01-01T12:00", "temperature_2m": 4.2, "relative_humidity_2m": 87}}`
+fn parse(body: &str) -> Option<Weather> {
+    let (_, current) = body.split_once("\"current\":")?;
+    Some(Weather {
+        temperature: value(current, "temperature_2m")?,
+        humidity: value(current, "relative_humidity_2m")?,
+    })
+}
+
+fn value(object: &str, key: &str) -> Option<f32> {
+    let (_, rest) = object.split_once(&format!("\"{}\":", key))?;
+    let end = rest.find(|c| c == ',' || c == '}')?;
+    rest[..end].trim().parse().ok()
+}
+
+/// Ventilating lowers the humidity only if the outdoor air carries less
+/// water, i.e. its dew point is lower than the one indoors
+pub fn ventilation_helps(indoor_dew_point: f32, outdoor_dew_point: f32) -> bool {
+    // a small margin keeps the advice from flapping
+    outdoor_dew_point + 1.0 < indoor_dew_point
+}
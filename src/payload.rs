@@ -0,0 +1,131 @@
+use core::fmt;
+
+/// Message formatted into a fixed-size buffer on the stack, so publishing a
+/// measurement does not touch the heap
+pub struct Payload<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+/// The formatted message did not fit into the buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflow(usize);
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "message exceeds {} bytes", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Overflow {}
+
+impl<const N: usize> Payload<N> {
+    pub fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Formats the arguments into a new payload, e.g. `Payload::format(format_args!(..))`
+    pub fn format(args: fmt::Arguments<'_>) -> Result<Self, Overflow> {
+        let mut payload = Self::new();
+        fmt::write(&mut payload, args).map_err(|_| Overflow(N))?;
+        Ok(payload)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only complete str slices are ever written
+        core::str::from_utf8(self.as_bytes()).unwrap_or_default()
+    }
+}
+
+impl<const N: usize> Default for Payload<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> fmt::Write for Payload<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Display for Payload<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Escapes the quotes, backslashes and control characters of a string placed
+/// between the quotes of a json string
+pub struct JsonStr<'a>(pub &'a str);
+
+impl fmt::Display for JsonStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut unescaped = 0;
+        for (i, c) in self.0.char_indices() {
+            let escaped = match c {
+                '"' => "\\\"",
+                '\\' => "\\\\",
+                '\n' => "\\n",
+                '\r' => "\\r",
+                '\t' => "\\t",
+                c if c.is_control() => "",
+                _ => continue,
+            };
+            f.write_str(&self.0[unescaped..i])?;
+            if escaped.is_empty() {
+                write!(f, "\\u{:04x}", c as u32)?;
+            } else {
+                f.write_str(escaped)?;
+            }
+            unescaped = i + c.len_utf8();
+        }
+        f.write_str(&self.0[unescaped..])
+    }
+}
+
+/// Escapes the backslashes, quotes and line feeds of a Prometheus label value
+pub struct LabelValue<'a>(pub &'a str);
+
+impl fmt::Display for LabelValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut unescaped = 0;
+        for (i, c) in self.0.char_indices() {
+            let escaped = match c {
+                '\\' => "\\\\",
+                '"' => "\\\"",
+                '\n' => "\\n",
+                _ => continue,
+            };
+            f.write_str(&self.0[unescaped..i])?;
+            f.write_str(escaped)?;
+            unescaped = i + 1;
+        }
+        f.write_str(&self.0[unescaped..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(LabelValue("living room").to_string(), "living room");
+        assert_eq!(LabelValue("a\\b\"c\nd").to_string(), "a\\\\b\\\"c\\nd");
+    }
+}
@@ -0,0 +1,38 @@
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys::EspError;
+use std::sync::Mutex;
+
+const NAMESPACE: &str = "postmortem";
+const KEY: &str = "panic";
+/// Longest panic message kept, including the terminating zero of the nvs string
+const MAX_LEN: usize = 256;
+
+/// Stores the reason of a panic in the nvs before the node restarts, so the
+/// next boot can report it. Returns the reason stored by the previous run.
+pub fn install(partition: EspDefaultNvsPartition) -> Result<Option<String>, EspError> {
+    let mut nvs: EspNvs<NvsDefault> = EspNvs::new(partition, NAMESPACE, true)?;
+    let mut buf = [0u8; MAX_LEN];
+    let last_panic = nvs.get_str(KEY, &mut buf)?.map(str::to_owned);
+    if last_panic.is_some() {
+        nvs.remove(KEY)?;
+    }
+
+    let nvs = Mutex::new(nvs);
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let mut reason = info.to_string();
+        if reason.len() >= MAX_LEN {
+            let mut end = MAX_LEN - 1;
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+            reason.truncate(end);
+        }
+        // a panic while the nvs is written must not deadlock the hook
+        if let Ok(mut nvs) = nvs.try_lock() {
+            let _ = nvs.set_str(KEY, &reason);
+        }
+        default_hook(info);
+    }));
+    Ok(last_panic)
+}
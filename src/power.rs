@@ -0,0 +1,88 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use esp_idf_svc::hal::reset::ResetReason;
+use std::time::Instant;
+
+/// Marks the retained counters as valid, the memory holds garbage after power on
+const MAGIC: u32 = 0x4d48_5a31;
+
+// kept in RTC memory, which is not initialized at startup and survives resets as
+// long as the RTC domain stays powered. The brownout detector resets the chip
+// without running any code, so the counters are updated every cycle instead of
+// being flushed when the supply voltage drops.
+#[link_section = ".rtc_noinit"]
+static RETAINED_MAGIC: AtomicU32 = AtomicU32::new(0);
+#[link_section = ".rtc_noinit"]
+static BOOTS: AtomicU32 = AtomicU32::new(0);
+#[link_section = ".rtc_noinit"]
+static POWER_LOSSES: AtomicU32 = AtomicU32::new(0);
+#[link_section = ".rtc_noinit"]
+static CYCLES: AtomicU32 = AtomicU32::new(0);
+
+/// What the node knows about the resets since the counters were last lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerHistory {
+    /// the node was reset by the brownout detector
+    pub power_loss: bool,
+    /// boots since the RTC memory was last lost
+    pub boots: u32,
+    /// brownout resets since the RTC memory was last lost
+    pub power_losses: u32,
+    /// measurement cycles completed before the reset
+    pub cycles_before_reset: u32,
+}
+
+impl PowerHistory {
+    /// Restores the counters of the previous run and starts counting anew
+    pub fn on_boot(reset_reason: ResetReason) -> Self {
+        if RETAINED_MAGIC.load(Ordering::Relaxed) != MAGIC {
+            BOOTS.store(0, Ordering::Relaxed);
+            POWER_LOSSES.store(0, Ordering::Relaxed);
+            CYCLES.store(0, Ordering::Relaxed);
+            RETAINED_MAGIC.store(MAGIC, Ordering::Relaxed);
+        }
+        let power_loss = reset_reason == ResetReason::Brownout;
+        if power_loss {
+            POWER_LOSSES.fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            power_loss,
+            boots: BOOTS.fetch_add(1, Ordering::Relaxed) + 1,
+            power_losses: POWER_LOSSES.load(Ordering::Relaxed),
+            cycles_before_reset: CYCLES.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the history as json object
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"power_loss\": {}, \"boots\": {}, \"power_losses\": {}, \"cycles_before_reset\": {}}}",
+            self.power_loss, self.boots, self.power_losses, self.cycles_before_reset
+        )
+    }
+}
+
+/// Counts a completed measurement cycle
+pub fn record_cycle() {
+    CYCLES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Integrates the power readings to the energy consumed since startup
+#[derive(Debug, Default)]
+pub struct EnergyMeter {
+    last: Option<(Instant, f32)>,
+    watt_hours: f32,
+}
+
+impl EnergyMeter {
+    /// Records a power reading in W and returns the energy in Wh
+    pub fn record(&mut self, power: f32) -> f32 {
+        let now = Instant::now();
+        if let Some((last, last_power)) = self.last {
+            // the average of both readings over the time in between
+            let hours = now.duration_since(last).as_secs_f32() / 3600.0;
+            self.watt_hours += (last_power + power) / 2.0 * hours;
+        }
+        self.last = Some((now, power));
+        self.watt_hours
+    }
+}
@@ -0,0 +1,149 @@
+use crate::config::{AppConfig, ConfigStore, Key};
+use crate::urlencoded;
+use anyhow::Result;
+use embedded_svc::http::Method;
+use embedded_svc::io::Write;
+use embedded_svc::wifi::{AccessPointConfiguration, AuthMethod, Configuration};
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::http::server::{self, EspHttpServer};
+use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// The form is small, longer requests are cut off
+const MAX_FORM_LEN: usize = 1024;
+/// Time the access point stays open without settings being entered, the node
+/// then tries the stored network again, e.g. after the router came back late
+/// from a power cut
+const TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Values entered in the form, the result of storing them is sent back
+type Submission = (Vec<(Key, String)>, mpsc::Sender<Result<(), String>>);
+
+/// Opens an access point named `co2-sensor-<location>` with a form to enter the
+/// wifi and MQTT settings, returns once they are stored in the nvs or after
+/// the timeout
+pub fn run(
+    esp_wifi: &mut EspWifi<'static>,
+    sysloop: EspSystemEventLoop,
+    store: &mut ConfigStore,
+    settings: &AppConfig,
+    location: &str,
+) -> Result<()> {
+    let ap_ssid = format!("co2-sensor-{}", location);
+    let mut wifi = BlockingWifi::wrap(esp_wifi, sysloop)?;
+    wifi.set_configuration(&Configuration::AccessPoint(AccessPointConfiguration {
+        ssid: ap_ssid
+            .parse()
+            .map_err(|_| anyhow::anyhow!("access point name too long: {}", ap_ssid))?,
+        auth_method: AuthMethod::None,
+        ..Default::default()
+    }))?;
+    wifi.start()?;
+    wifi.wait_netif_up()?;
+    let ip_info = wifi.wifi().ap_netif().get_ip_info()?;
+    log::warn!(
+        "Provisioning mode, connect to {} and open http://{}",
+        ap_ssid,
+        ip_info.ip
+    );
+
+    let (entered, submissions) = mpsc::channel::<Submission>();
+    let mut server = EspHttpServer::new(&server::Configuration::default())?;
+    let form = form(settings);
+    server.fn_handler("/", Method::Get, move |request| {
+        request.into_ok_response()?.write_all(form.as_bytes())?;
+        Ok(())
+    })?;
+    server.fn_handler("/", Method::Post, move |mut request| {
+        let mut body = [0u8; MAX_FORM_LEN];
+        let mut len = 0;
+        while len < body.len() {
+            match request.read(&mut body[len..])? {
+                0 => break,
+                read => len += read,
+            }
+        }
+        let mut fields = Vec::new();
+        for (name, value) in core::str::from_utf8(&body[..len])?
+            .split('&')
+            .filter_map(|field| field.split_once('='))
+        {
+            let value = urlencoded::decode(value);
+            match Key::parse(name) {
+                // empty passwords keep the current ones
+                Some(Key::WifiPsk | Key::MqttPass) if value.is_empty() => {}
+                Some(
+                    key @ (Key::WifiSsid
+                    | Key::WifiPsk
+                    | Key::MqttHost
+                    | Key::MqttUser
+                    | Key::MqttPass),
+                ) => fields.push((key, value)),
+                _ => {}
+            }
+        }
+        // the answer waits until the values are stored
+        let (stored, result) = mpsc::channel();
+        entered.send((fields, stored))?;
+        match result.recv()? {
+            Ok(()) => request
+                .into_ok_response()?
+                .write_all(b"<html><body>Saved, the sensor restarts.</body></html>")?,
+            Err(err) => request.into_status_response(500)?.write_all(
+                format!("<html><body>Not saved: {}</body></html>", html_escape(&err)).as_bytes(),
+            )?,
+        }
+        Ok(())
+    })?;
+
+    // keep the server running until valid settings are stored, only the values
+    // of the form are stored so the others keep following cfg.toml
+    let deadline = Instant::now() + TIMEOUT;
+    while let Ok((fields, stored)) =
+        submissions.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+    {
+        match fields
+            .iter()
+            .try_for_each(|(key, value)| store.set(*key, value))
+        {
+            Ok(()) => {
+                log::info!("Configuration entered in provisioning mode stored");
+                let _ = stored.send(Ok(()));
+                return Ok(());
+            }
+            Err(err) => {
+                log::warn!("error storing configuration: {:}", err);
+                let _ = stored.send(Err(err.to_string()));
+            }
+        }
+    }
+    log::warn!(
+        "No configuration entered within {} minutes",
+        TIMEOUT.as_secs() / 60
+    );
+    Ok(())
+}
+
+/// Html form prefilled with the current settings, passwords are left out
+fn form(settings: &AppConfig) -> String {
+    format!(
+        "<html><body><h1>CO2 sensor setup</h1><form method=\"post\" action=\"/\">\
+         <p>WiFi name <input name=\"wifi_ssid\" value=\"{}\"></p>\
+         <p>WiFi password <input name=\"wifi_psk\" type=\"password\"></p>\
+         <p>MQTT broker <input name=\"mqtt_host\" value=\"{}\"></p>\
+         <p>MQTT user <input name=\"mqtt_user\" value=\"{}\"></p>\
+         <p>MQTT password <input name=\"mqtt_pass\" type=\"password\"></p>\
+         <p><input type=\"submit\" value=\"Save\"></p></form></body></html>",
+        html_escape(&settings.wifi_ssid),
+        html_escape(&settings.mqtt_host),
+        html_escape(&settings.mqtt_user)
+    )
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
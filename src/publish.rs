@@ -0,0 +1,323 @@
+use crate::derived;
+use crate::ina219::PowerReading;
+use crate::measurement::Measurement;
+use crate::mqtt::MqttPublisher;
+use crate::outdoor::{self, Weather};
+use crate::payload::{JsonStr, Payload};
+use crate::sensors::TempHum;
+use crate::stats::Stats;
+use embedded_svc::mqtt::client::QoS;
+
+/// Publishes the json of all values, with `legacy_topics` also the plain value
+/// topics of older releases. Nothing is published while the broker is not
+/// connected, the history publishes the json late instead.
+pub fn publish_measurement(
+    client: &mut MqttPublisher,
+    location: &str,
+    measurement: &Measurement,
+    legacy_topics: bool,
+) {
+    if !client.is_connected() {
+        return;
+    }
+    publish_json(client, location, measurement);
+    if !legacy_topics {
+        return;
+    }
+    if let Some(co2) = measurement.co2() {
+        publish_co2(client, location, co2);
+        publish_value(client, location, "co2", format_args!("{}", co2));
+    }
+    if let (Some(temperature), Some(humidity)) = (measurement.temperature(), measurement.humidity())
+    {
+        let pressure = measurement.pressure().unwrap_or(0.0);
+        publish_climate(client, location, temperature, humidity, pressure);
+    }
+    if let Some(temperature) = measurement.temperature() {
+        publish_value(
+            client,
+            location,
+            "temperature",
+            format_args!("{}", temperature),
+        );
+    }
+    if let Some(humidity) = measurement.humidity() {
+        publish_value(client, location, "humidity", format_args!("{}", humidity));
+    }
+    if let Some(pressure) = measurement.pressure() {
+        publish_value(client, location, "pressure", format_args!("{}", pressure));
+    }
+    if let (Some(dew_point), Some(absolute_humidity), Some(heat_index)) = (
+        measurement.dew_point(),
+        measurement.absolute_humidity(),
+        measurement.heat_index(),
+    ) {
+        publish_value(
+            client,
+            location,
+            "dew_point",
+            format_args!("{:.1}", dew_point),
+        );
+        publish_value(
+            client,
+            location,
+            "absolute_humidity",
+            format_args!("{:.1}", absolute_humidity),
+        );
+        publish_value(
+            client,
+            location,
+            "heat_index",
+            format_args!("{:.1}", heat_index),
+        );
+    }
+}
+
+/// Publishes the values of an additional DHT to `home/<location>/<id>/<quantity>`
+pub fn publish_extra_climate(
+    client: &mut MqttPublisher,
+    location: &str,
+    id: &str,
+    climate: TempHum,
+) {
+    log::info!("DHT {}: {:?}", id, climate);
+    publish_value(
+        client,
+        location,
+        &format!("{}/temperature", id),
+        format_args!("{}", climate.temperature),
+    );
+    if let Some(humidity) = climate.humidity {
+        publish_value(
+            client,
+            location,
+            &format!("{}/humidity", id),
+            format_args!("{}", humidity),
+        );
+    }
+}
+
+/// Publishes all values as a single json object to `home/<location>/measurement`
+pub fn publish_json(client: &mut MqttPublisher, location: &str, measurement: &Measurement) {
+    // a cycle without any reading has nothing to tell
+    if measurement.is_empty() {
+        return;
+    }
+    let (topic, measurement_msg) = match (
+        Payload::<96>::format(format_args!("home/{}/measurement", location)),
+        Payload::<160>::format(format_args!("{}", measurement)),
+    ) {
+        (Ok(topic), Ok(measurement_msg)) => (topic, measurement_msg),
+        (Err(err), _) | (_, Err(err)) => {
+            log::warn!("error encoding measurement: {:}", err);
+            return;
+        }
+    };
+    let publ_status = client.publish(
+        topic.as_str(),
+        QoS::AtLeastOnce,
+        false,
+        measurement_msg.as_bytes(),
+    );
+    if let Err(err) = publ_status {
+        log::warn!("error publishing measurement: {:}", err);
+    }
+}
+
+/// Publishes a single value as plain text to `home/<location>/<quantity>`
+pub fn publish_value(
+    client: &mut MqttPublisher,
+    location: &str,
+    quantity: &str,
+    value: core::fmt::Arguments<'_>,
+) {
+    let (topic, value_msg) = match (
+        Payload::<96>::format(format_args!("home/{}/{}", location, quantity)),
+        Payload::<16>::format(value),
+    ) {
+        (Ok(topic), Ok(value_msg)) => (topic, value_msg),
+        (Err(err), _) | (_, Err(err)) => {
+            log::warn!("error encoding {} value: {:}", quantity, err);
+            return;
+        }
+    };
+    let publ_status = client.publish(
+        topic.as_str(),
+        QoS::AtLeastOnce,
+        false,
+        value_msg.as_bytes(),
+    );
+    if let Err(err) = publ_status {
+        log::warn!("error publishing {} value: {:}", quantity, err);
+    }
+}
+
+pub fn publish_co2(client: &mut MqttPublisher, location: &str, co2: i32) {
+    let co2_msg = match Payload::<128>::format(format_args!(
+        "{{\"location\": \"{}\", \"co2\": {:}}}",
+        JsonStr(location),
+        co2
+    )) {
+        Ok(co2_msg) => co2_msg,
+        Err(err) => {
+            log::warn!("error encoding CO2 data: {:}", err);
+            return;
+        }
+    };
+    let publ_status = client.publish("home/data/co2", QoS::AtLeastOnce, false, co2_msg.as_bytes());
+    match publ_status {
+        Ok(_) => {}
+        Err(err) => log::warn!("error publishing CO2 data: {:}", err),
+    };
+}
+
+pub fn publish_climate(
+    client: &mut MqttPublisher,
+    location: &str,
+    temperature: f32,
+    humidity: f32,
+    pressure: f32,
+) {
+    let ambient_data_msg = match Payload::<160>::format(format_args!(
+        "{{\"temperature\": {:}, \"humidity\": {:}, \"pressure\": {:}, \"location\": \"{}\"}}",
+        temperature,
+        humidity,
+        pressure,
+        JsonStr(location)
+    )) {
+        Ok(ambient_data_msg) => ambient_data_msg,
+        Err(err) => {
+            log::warn!("error encoding climate data: {:}", err);
+            return;
+        }
+    };
+    let publ_status = client.publish(
+        "home/data/climate",
+        QoS::AtLeastOnce,
+        false,
+        ambient_data_msg.as_bytes(),
+    );
+    match publ_status {
+        Ok(_) => {}
+        Err(err) => log::warn!("error publishing climate data: {:}", err),
+    };
+}
+
+/// Publishes the fault flag of a sensor retained to `home/<location>/<sensor>/fault`
+pub fn report_fault(client: &mut MqttPublisher, location: &str, sensor: &str, faulted: bool) {
+    if faulted {
+        log::warn!(
+            "The {} sensor failed repeatedly, it is only probed until it recovers",
+            sensor
+        );
+    } else {
+        log::info!("The {} sensor reads fine", sensor);
+    }
+    let topic = match Payload::<96>::format(format_args!("home/{}/{}/fault", location, sensor)) {
+        Ok(topic) => topic,
+        Err(err) => {
+            log::warn!("error encoding {} fault: {:}", sensor, err);
+            return;
+        }
+    };
+    let fault_msg = if faulted { "true" } else { "false" };
+    let publ_status = client.publish(topic.as_str(), QoS::AtLeastOnce, true, fault_msg.as_bytes());
+    if let Err(err) = publ_status {
+        log::warn!("error publishing {} fault: {:}", sensor, err);
+    }
+}
+
+/// Publishes the error counters of the sensors to `home/data/stats`
+pub fn publish_stats(client: &mut MqttPublisher, location: &str, stats: &Stats) {
+    let stats_msg = match Payload::<512>::format(format_args!(
+        "{{\"location\": \"{}\", \"co2\": {}, \"climate\": {}}}",
+        JsonStr(location),
+        stats.co2,
+        stats.climate
+    )) {
+        Ok(stats_msg) => stats_msg,
+        Err(err) => {
+            log::warn!("error encoding sensor stats: {:}", err);
+            return;
+        }
+    };
+    log::info!("Sensor stats: {}", stats_msg);
+    let publ_status = client.publish(
+        "home/data/stats",
+        QoS::AtMostOnce,
+        false,
+        stats_msg.as_bytes(),
+    );
+    if let Err(err) = publ_status {
+        log::warn!("error publishing sensor stats: {:}", err);
+    }
+}
+
+pub fn publish_power(
+    client: &mut MqttPublisher,
+    location: &str,
+    reading: PowerReading,
+    energy_wh: f32,
+) {
+    let power_msg = match Payload::<192>::format(format_args!(
+        "{{\"location\": \"{}\", \"voltage\": {:.3}, \"current\": {:.4}, \"power\": {:.3}, \"energy_wh\": {:.3}}}",
+        JsonStr(location),
+        reading.voltage,
+        reading.current,
+        reading.power(),
+        energy_wh
+    )) {
+        Ok(power_msg) => power_msg,
+        Err(err) => {
+            log::warn!("error encoding power data: {:}", err);
+            return;
+        }
+    };
+    let publ_status = client.publish(
+        "home/data/power",
+        QoS::AtLeastOnce,
+        false,
+        power_msg.as_bytes(),
+    );
+    match publ_status {
+        Ok(_) => {}
+        Err(err) => log::warn!("error publishing power data: {:}", err),
+    };
+}
+
+pub fn publish_outdoor(
+    client: &mut MqttPublisher,
+    location: &str,
+    temperature: f32,
+    humidity: f32,
+    outdoor: Weather,
+) {
+    let dew_point = derived::dew_point(temperature, humidity);
+    let outdoor_msg = match Payload::<384>::format(format_args!(
+        "{{\"location\": \"{}\", \"outdoor_temperature\": {:.1}, \"outdoor_humidity\": {:.0}, \"temperature_delta\": {:.1}, \"humidity_delta\": {:.1}, \"dew_point\": {:.1}, \"outdoor_dew_point\": {:.1}, \"ventilation_helps\": {}}}",
+        JsonStr(location),
+        outdoor.temperature,
+        outdoor.humidity,
+        temperature - outdoor.temperature,
+        humidity - outdoor.humidity,
+        dew_point,
+        outdoor.dew_point(),
+        outdoor::ventilation_helps(dew_point, outdoor.dew_point())
+    )) {
+        Ok(outdoor_msg) => outdoor_msg,
+        Err(err) => {
+            log::warn!("error encoding outdoor comparison: {:}", err);
+            return;
+        }
+    };
+    let publ_status = client.publish(
+        "home/data/outdoor",
+        QoS::AtLeastOnce,
+        false,
+        outdoor_msg.as_bytes(),
+    );
+    match publ_status {
+        Ok(_) => {}
+        Err(err) => log::warn!("error publishing outdoor comparison: {:}", err),
+    };
+}
@@ -0,0 +1,70 @@
+use crate::measurement::Measurement;
+use crate::sensors::TempHum;
+use std::time::{Duration, Instant};
+
+/// A sensor value together with the time it was read
+#[derive(Debug, Clone, Copy)]
+struct Sample<T> {
+    value: T,
+    time: Instant,
+}
+
+/// Fuses the values of the individual sensors into a single snapshot and
+/// makes sure the values of one snapshot were read close together
+pub struct SamplingCoordinator {
+    max_age: Duration,
+    co2: Option<Sample<i32>>,
+    climate: Option<Sample<TempHum>>,
+}
+
+impl SamplingCoordinator {
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            co2: None,
+            climate: None,
+        }
+    }
+
+    pub fn record_co2(&mut self, co2: i32) {
+        self.co2 = Some(Sample {
+            value: co2,
+            time: Instant::now(),
+        });
+    }
+
+    pub fn record_climate(&mut self, climate: TempHum) {
+        self.climate = Some(Sample {
+            value: climate,
+            time: Instant::now(),
+        });
+    }
+
+    /// Returns the snapshot of all values not older than the maximum sample age,
+    /// values missing or older are marked invalid
+    pub fn snapshot(&mut self, timestamp: Option<u64>) -> Measurement {
+        let now = Instant::now();
+        let co2 = self
+            .co2
+            .take()
+            .filter(|sample| now.duration_since(sample.time) <= self.max_age);
+        let climate = self
+            .climate
+            .take()
+            .filter(|sample| now.duration_since(sample.time) <= self.max_age);
+        let mut measurement = Measurement::new(timestamp);
+        if let Some(sample) = co2 {
+            measurement.set_co2(sample.value);
+        }
+        if let Some(Sample { value: climate, .. }) = climate {
+            measurement.set_temperature(climate.temperature);
+            if let Some(humidity) = climate.humidity {
+                measurement.set_humidity(humidity);
+            }
+            if let Some(pressure) = climate.pressure {
+                measurement.set_pressure(pressure);
+            }
+        }
+        measurement
+    }
+}
@@ -0,0 +1,240 @@
+use crate::sensors::Co2Sensor;
+use core::fmt;
+use embedded_hal::delay::DelayUs;
+use embedded_hal::i2c::I2c;
+
+pub const SCD30_ADDRESS: u8 = 0x61;
+pub const SCD4X_ADDRESS: u8 = 0x62;
+
+#[derive(Debug)]
+pub enum ScdError<HE> {
+    /// no new measurement is available since the last read
+    NotReady,
+    /// crc mismatch of a received word (expected, received)
+    Crc(u8, u8),
+    /// Error of underlying IO
+    HalError(HE),
+}
+
+impl<HE> From<HE> for ScdError<HE> {
+    fn from(error: HE) -> Self {
+        ScdError::HalError(error)
+    }
+}
+
+impl<HE: fmt::Debug> fmt::Display for ScdError<HE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ScdError::*;
+        match self {
+            NotReady => write!(f, "No new measurement available"),
+            Crc(exp, act) => write!(f, "CRC error: {:x} vs {:x}", exp, act),
+            HalError(err) => write!(f, "HAL error: {:?}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<HE: fmt::Debug> std::error::Error for ScdError<HE> {}
+
+/// Measurement of a Sensirion co2 sensor, which measures the temperature
+/// and humidity as well
+#[derive(Debug, Clone, Copy)]
+pub struct ScdReadout {
+    pub co2_ppm: i32,
+    pub temperature: f32,
+    pub humidity: f32,
+}
+
+/// CRC-8 of the Sensirion sensors over every 16 bit word
+pub(crate) fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xffu8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x31
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Frames a command with an argument word and its crc
+fn with_argument(command: u16, argument: u16) -> [u8; 5] {
+    let [cmd_msb, cmd_lsb] = command.to_be_bytes();
+    let [arg_msb, arg_lsb] = argument.to_be_bytes();
+    [
+        cmd_msb,
+        cmd_lsb,
+        arg_msb,
+        arg_lsb,
+        crc8(&[arg_msb, arg_lsb]),
+    ]
+}
+
+/// Checks the crc of the received words of 2 bytes followed by their crc
+/// and returns the words
+fn to_words<HE, const N: usize>(buf: &[u8]) -> Result<[u16; N], ScdError<HE>> {
+    let mut words = [0u16; N];
+    for (word, chunk) in words.iter_mut().zip(buf.chunks_exact(3)) {
+        let crc = crc8(&chunk[..2]);
+        if crc != chunk[2] {
+            return Err(ScdError::Crc(crc, chunk[2]));
+        }
+        *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+    }
+    Ok(words)
+}
+
+/// Sensirion SCD30 NDIR co2 sensor, the i2c bus has to run at 100 kHz at most
+pub struct Scd30<HE, I: I2c<Error = HE>> {
+    i2c: I,
+}
+
+impl<HE, I: I2c<Error = HE>> Scd30<HE, I> {
+    pub fn new(i2c: I) -> Self {
+        Self { i2c }
+    }
+
+    /// Starts measuring continuously every `interval_s` (2 to 1800) seconds,
+    /// without compensating the ambient pressure
+    pub fn start_measurement(&mut self, interval_s: u16) -> Result<(), ScdError<HE>> {
+        self.i2c.write(
+            SCD30_ADDRESS,
+            &with_argument(0x4600, interval_s.clamp(2, 1800)),
+        )?;
+        self.i2c.write(SCD30_ADDRESS, &with_argument(0x0010, 0))?;
+        Ok(())
+    }
+
+    /// Enables or disables the automatic self calibration, which assumes
+    /// fresh air at 400 ppm once a day
+    pub fn set_auto_calibration(&mut self, enable: bool) -> Result<(), ScdError<HE>> {
+        self.i2c
+            .write(SCD30_ADDRESS, &with_argument(0x5306, enable as u16))?;
+        Ok(())
+    }
+
+    pub fn data_ready(&mut self, delay: &mut impl DelayUs) -> Result<bool, ScdError<HE>> {
+        let [ready] = self.read_words::<1, 3>(0x0202, delay)?;
+        Ok(ready == 1)
+    }
+
+    /// Reads the latest measurement, `ScdError::NotReady` is returned if no
+    /// new one is available
+    pub fn read_measurement(
+        &mut self,
+        delay: &mut impl DelayUs,
+    ) -> Result<ScdReadout, ScdError<HE>> {
+        if !self.data_ready(delay)? {
+            return Err(ScdError::NotReady);
+        }
+        // three big endian floats of two words each
+        let words = self.read_words::<6, 18>(0x0300, delay)?;
+        let value = |i: usize| f32::from_bits((words[i] as u32) << 16 | words[i + 1] as u32);
+        Ok(ScdReadout {
+            co2_ppm: value(0) as i32,
+            temperature: value(2),
+            humidity: value(4),
+        })
+    }
+
+    fn read_words<const N: usize, const B: usize>(
+        &mut self,
+        command: u16,
+        delay: &mut impl DelayUs,
+    ) -> Result<[u16; N], ScdError<HE>> {
+        self.i2c.write(SCD30_ADDRESS, &command.to_be_bytes())?;
+        // the sensor needs 3 ms to prepare the answer
+        delay.delay_ms(3);
+        let mut buf = [0u8; B];
+        self.i2c.read(SCD30_ADDRESS, &mut buf)?;
+        to_words(&buf)
+    }
+}
+
+impl<HE, I: I2c<Error = HE>> Co2Sensor for Scd30<HE, I> {
+    type Error = ScdError<HE>;
+
+    fn read_ppm(&mut self, mut delay: &mut dyn DelayUs) -> Result<i32, ScdError<HE>> {
+        Ok(self.read_measurement(&mut delay)?.co2_ppm)
+    }
+}
+
+/// Sensirion SCD40/SCD41 photoacoustic co2 sensor
+pub struct Scd4x<HE, I: I2c<Error = HE>> {
+    i2c: I,
+}
+
+impl<HE, I: I2c<Error = HE>> Scd4x<HE, I> {
+    pub fn new(i2c: I) -> Self {
+        Self { i2c }
+    }
+
+    /// Starts measuring every 5 seconds
+    pub fn start_measurement(&mut self) -> Result<(), ScdError<HE>> {
+        self.i2c.write(SCD4X_ADDRESS, &[0x21, 0xb1])?;
+        Ok(())
+    }
+
+    /// Stops measuring, the sensor ignores most commands while measuring,
+    /// e.g. if the measurement was started before a restart
+    pub fn stop_measurement(&mut self, delay: &mut impl DelayUs) -> Result<(), ScdError<HE>> {
+        self.i2c.write(SCD4X_ADDRESS, &[0x3f, 0x86])?;
+        delay.delay_ms(500);
+        Ok(())
+    }
+
+    /// Enables or disables the automatic self calibration, only while not
+    /// measuring
+    pub fn set_auto_calibration(&mut self, enable: bool) -> Result<(), ScdError<HE>> {
+        self.i2c
+            .write(SCD4X_ADDRESS, &with_argument(0x2416, enable as u16))?;
+        Ok(())
+    }
+
+    pub fn data_ready(&mut self, delay: &mut impl DelayUs) -> Result<bool, ScdError<HE>> {
+        let [status] = self.read_words::<1, 3>(0xe4b8, delay)?;
+        Ok(status & 0x07ff != 0)
+    }
+
+    /// Reads the latest measurement, `ScdError::NotReady` is returned if no
+    /// new one is available
+    pub fn read_measurement(
+        &mut self,
+        delay: &mut impl DelayUs,
+    ) -> Result<ScdReadout, ScdError<HE>> {
+        if !self.data_ready(delay)? {
+            return Err(ScdError::NotReady);
+        }
+        let [co2, temperature, humidity] = self.read_words::<3, 9>(0xec05, delay)?;
+        Ok(ScdReadout {
+            co2_ppm: co2 as i32,
+            temperature: -45.0 + 175.0 * temperature as f32 / 65535.0,
+            humidity: 100.0 * humidity as f32 / 65535.0,
+        })
+    }
+
+    fn read_words<const N: usize, const B: usize>(
+        &mut self,
+        command: u16,
+        delay: &mut impl DelayUs,
+    ) -> Result<[u16; N], ScdError<HE>> {
+        self.i2c.write(SCD4X_ADDRESS, &command.to_be_bytes())?;
+        // the sensor needs 1 ms to prepare the answer
+        delay.delay_ms(1);
+        let mut buf = [0u8; B];
+        self.i2c.read(SCD4X_ADDRESS, &mut buf)?;
+        to_words(&buf)
+    }
+}
+
+impl<HE, I: I2c<Error = HE>> Co2Sensor for Scd4x<HE, I> {
+    type Error = ScdError<HE>;
+
+    fn read_ppm(&mut self, mut delay: &mut dyn DelayUs) -> Result<i32, ScdError<HE>> {
+        Ok(self.read_measurement(&mut delay)?.co2_ppm)
+    }
+}
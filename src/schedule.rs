@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+/// Cycles of the loop drift by a few milliseconds, a task is also due if the
+/// cycle comes slightly before its interval has passed
+const SLACK: Duration = Duration::from_secs(1);
+
+/// Runs a task of the measurement loop at its own interval. The loop wakes up
+/// at the measurement interval, a task is due in the first cycle its interval
+/// after the last run, a zero interval runs it every cycle.
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    interval: Duration,
+    last_run: Option<Instant>,
+}
+
+impl Schedule {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_run: None,
+        }
+    }
+
+    /// Returns true and records the run if the task is due
+    pub fn is_due(&mut self, now: Instant) -> bool {
+        let due = self.last_run.map_or(true, |last_run| {
+            now.duration_since(last_run) + SLACK >= self.interval
+        });
+        if due {
+            self.last_run = Some(now);
+        }
+        due
+    }
+
+    /// Starts the interval anew, the task is next due one interval after now
+    pub fn restart(&mut self, now: Instant) {
+        self.last_run = Some(now);
+    }
+}
+
+/// Parses the intervals of named tasks in seconds, e.g. `influx=900,display=60`,
+/// invalid entries are skipped with a warning
+pub fn parse_intervals(value: &str) -> Vec<(&str, Duration)> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once('=')
+                .and_then(|(name, seconds)| Some((name.trim(), seconds.trim().parse().ok()?)));
+            if parsed.is_none() {
+                log::warn!("Invalid interval {}, expected <name>=<seconds>", entry);
+            }
+            parsed.map(|(name, seconds)| (name, Duration::from_secs(seconds)))
+        })
+        .collect()
+}
@@ -0,0 +1,52 @@
+use crate::payload::JsonStr;
+use core::fmt;
+use embedded_hal::i2c::{Error, I2c};
+
+/// Pass/fail results of probing the peripherals
+#[derive(Debug, Default)]
+pub struct SelfTestReport {
+    checks: Vec<(&'static str, Result<(), String>)>,
+}
+
+impl SelfTestReport {
+    /// Records the outcome of probing the given peripheral
+    pub fn check<T, E: fmt::Display>(&mut self, name: &'static str, result: Result<T, E>) {
+        let result = result.map(|_| ()).map_err(|err| err.to_string());
+        match &result {
+            Ok(()) => log::info!("selftest {}: pass", name),
+            Err(err) => log::warn!("selftest {}: fail ({})", name, err),
+        }
+        self.checks.push((name, result));
+    }
+
+    /// Records whether the device acknowledges its address on the i2c bus
+    pub fn probe_i2c<I: I2c>(&mut self, name: &'static str, bus: &mut I, address: u8) {
+        let result = bus
+            .write(address, &[])
+            .map_err(|err| format!("no ack at 0x{:02x} ({:?})", address, err.kind()));
+        self.check(name, result);
+    }
+
+    /// Returns true if all probed peripherals responded correctly
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|(_, result)| result.is_ok())
+    }
+
+    /// Returns the report as json object
+    pub fn to_json(&self) -> String {
+        let checks = self
+            .checks
+            .iter()
+            .map(|(name, result)| match result {
+                Ok(()) => format!("\"{}\": \"pass\"", name),
+                Err(err) => format!("\"{}\": \"fail: {}\"", name, JsonStr(err)),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "{{\"passed\": {}, \"checks\": {{{}}}}}",
+            self.passed(),
+            checks
+        )
+    }
+}
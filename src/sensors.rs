@@ -0,0 +1,119 @@
+use embedded_hal::delay::DelayUs;
+
+/// A sensor measuring the co2 concentration, implemented by the `MHz19` and
+/// the Sensirion `Scd30` and `Scd4x`
+pub trait Co2Sensor {
+    type Error;
+
+    /// Returns the co2 concentration in ppm, the delay is used while waiting
+    /// for the answer of the sensor
+    fn read_ppm(&mut self, delay: &mut dyn DelayUs) -> Result<i32, Self::Error>;
+}
+
+/// Ambient temperature, humidity and pressure, the values a sensor does not
+/// measure are `None`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempHum {
+    /// degree celsius
+    pub temperature: f32,
+    /// relative humidity in the range of 0..100%
+    pub humidity: Option<f32>,
+    /// hPa
+    pub pressure: Option<f32>,
+}
+
+/// A sensor measuring the ambient temperature and humidity, implemented by
+/// the readers of the DHT family and the `Bme280`
+pub trait TempHumSensor {
+    type Error;
+
+    /// Returns the temperature and humidity, the delay is used while waiting
+    /// for the answer of the sensor
+    fn read_temp_hum(&mut self, delay: &mut dyn DelayUs) -> Result<TempHum, Self::Error>;
+}
+
+#[cfg(feature = "std")]
+impl<S: TempHumSensor + ?Sized> TempHumSensor for Box<S> {
+    type Error = S::Error;
+
+    fn read_temp_hum(&mut self, delay: &mut dyn DelayUs) -> Result<TempHum, S::Error> {
+        (**self).read_temp_hum(delay)
+    }
+}
+
+/// Statistics of the reads with retries
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetryStats {
+    /// reads through `Retrying`
+    pub reads: u32,
+    /// reads repeated after an error
+    pub retries: u32,
+    /// reads for which all attempts failed
+    pub failures: u32,
+}
+
+/// Repeats failed reads of a sensor up to the given number of attempts,
+/// spaced by the backoff. The error of the last attempt is returned if all
+/// of them fail.
+pub struct Retrying<S> {
+    sensor: S,
+    attempts: u32,
+    backoff_ms: u32,
+    stats: RetryStats,
+}
+
+impl<S> Retrying<S> {
+    pub fn new(sensor: S, attempts: u32, backoff_ms: u32) -> Self {
+        Self {
+            sensor,
+            attempts,
+            backoff_ms,
+            stats: RetryStats::default(),
+        }
+    }
+
+    /// Returns the sensor for reads without retries
+    pub fn sensor(&mut self) -> &mut S {
+        &mut self.sensor
+    }
+
+    pub fn stats(&self) -> RetryStats {
+        self.stats
+    }
+
+    /// Calls `read` until it succeeds or the attempts are used up. It gets the
+    /// sensor and the time in ms to wait before the attempt, 0 for the first.
+    pub fn retry<T, E>(
+        &mut self,
+        mut read: impl FnMut(&mut S, u32) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.stats.reads += 1;
+        let mut attempt = 1;
+        let mut wait_ms = 0;
+        loop {
+            match read(&mut self.sensor, wait_ms) {
+                Ok(data) => return Ok(data),
+                Err(err) if attempt >= self.attempts => {
+                    self.stats.failures += 1;
+                    return Err(err);
+                }
+                Err(_) => {
+                    self.stats.retries += 1;
+                    attempt += 1;
+                    wait_ms = self.backoff_ms;
+                }
+            }
+        }
+    }
+}
+
+impl<S: TempHumSensor> TempHumSensor for Retrying<S> {
+    type Error = S::Error;
+
+    fn read_temp_hum(&mut self, delay: &mut dyn DelayUs) -> Result<TempHum, S::Error> {
+        self.retry(|sensor, wait_ms| {
+            delay.delay_ms(wait_ms);
+            sensor.read_temp_hum(delay)
+        })
+    }
+}
@@ -0,0 +1,153 @@
+use crate::scd::crc8;
+use crate::sensors::{TempHum, TempHumSensor};
+use core::fmt;
+use embedded_hal::delay::DelayUs;
+use embedded_hal::i2c::I2c;
+
+/// Address of the SHT4x and of the SHT3x with ADDR tied to ground, with ADDR
+/// tied to VDD the SHT3x answers on 0x45
+pub const DEFAULT_ADDRESS: u8 = 0x44;
+
+#[derive(Debug)]
+pub enum ShtError<HE> {
+    /// crc mismatch of a received word (expected, received)
+    Crc(u8, u8),
+    /// Error of underlying IO
+    HalError(HE),
+}
+
+impl<HE> From<HE> for ShtError<HE> {
+    fn from(error: HE) -> Self {
+        ShtError::HalError(error)
+    }
+}
+
+impl<HE: fmt::Debug> fmt::Display for ShtError<HE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ShtError::*;
+        match self {
+            Crc(exp, act) => write!(f, "CRC error: {:x} vs {:x}", exp, act),
+            HalError(err) => write!(f, "HAL error: {:?}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<HE: fmt::Debug> std::error::Error for ShtError<HE> {}
+
+/// Repeatability of the measurement, a higher one takes longer and heats
+/// the sensor a little more
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    High,
+    Medium,
+    Low,
+}
+
+/// Sends the single shot command, waits for the conversion and returns the
+/// raw temperature and humidity words
+fn measure<HE, I: I2c<Error = HE>>(
+    i2c: &mut I,
+    address: u8,
+    command: &[u8],
+    duration_ms: u32,
+    delay: &mut impl DelayUs,
+) -> Result<(u16, u16), ShtError<HE>> {
+    i2c.write(address, command)?;
+    delay.delay_ms(duration_ms);
+    let mut buf = [0u8; 6];
+    i2c.read(address, &mut buf)?;
+    let mut words = [0u16; 2];
+    for (word, chunk) in words.iter_mut().zip(buf.chunks_exact(3)) {
+        let crc = crc8(&chunk[..2]);
+        if crc != chunk[2] {
+            return Err(ShtError::Crc(crc, chunk[2]));
+        }
+        *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+    }
+    Ok((words[0], words[1]))
+}
+
+/// Sensirion SHT30/SHT31/SHT35 humidity and temperature sensor
+pub struct Sht3x<HE, I: I2c<Error = HE>> {
+    i2c: I,
+    address: u8,
+    precision: Precision,
+}
+
+impl<HE, I: I2c<Error = HE>> Sht3x<HE, I> {
+    pub fn new(i2c: I, address: u8, precision: Precision) -> Self {
+        Self {
+            i2c,
+            address,
+            precision,
+        }
+    }
+
+    /// Runs a single shot measurement without clock stretching
+    pub fn read_measurement(&mut self, delay: &mut impl DelayUs) -> Result<TempHum, ShtError<HE>> {
+        let (command, duration_ms) = match self.precision {
+            Precision::High => ([0x24, 0x00], 16),
+            Precision::Medium => ([0x24, 0x0b], 7),
+            Precision::Low => ([0x24, 0x16], 5),
+        };
+        let (temperature, humidity) =
+            measure(&mut self.i2c, self.address, &command, duration_ms, delay)?;
+        Ok(TempHum {
+            temperature: -45.0 + 175.0 * temperature as f32 / 65535.0,
+            humidity: Some(100.0 * humidity as f32 / 65535.0),
+            pressure: None,
+        })
+    }
+}
+
+impl<HE, I: I2c<Error = HE>> TempHumSensor for Sht3x<HE, I> {
+    type Error = ShtError<HE>;
+
+    fn read_temp_hum(&mut self, mut delay: &mut dyn DelayUs) -> Result<TempHum, ShtError<HE>> {
+        self.read_measurement(&mut delay)
+    }
+}
+
+/// Sensirion SHT40/SHT41/SHT45 humidity and temperature sensor
+pub struct Sht4x<HE, I: I2c<Error = HE>> {
+    i2c: I,
+    address: u8,
+    precision: Precision,
+}
+
+impl<HE, I: I2c<Error = HE>> Sht4x<HE, I> {
+    pub fn new(i2c: I, address: u8, precision: Precision) -> Self {
+        Self {
+            i2c,
+            address,
+            precision,
+        }
+    }
+
+    /// Runs a single measurement without activating the heater
+    pub fn read_measurement(&mut self, delay: &mut impl DelayUs) -> Result<TempHum, ShtError<HE>> {
+        let (command, duration_ms) = match self.precision {
+            Precision::High => (0xfd, 9),
+            Precision::Medium => (0xf6, 5),
+            Precision::Low => (0xe0, 2),
+        };
+        let (temperature, humidity) =
+            measure(&mut self.i2c, self.address, &[command], duration_ms, delay)?;
+        // the range of the humidity exceeds 0..100% slightly, so it is cropped
+        let humidity = -6.0 + 125.0 * humidity as f32 / 65535.0;
+        Ok(TempHum {
+            temperature: -45.0 + 175.0 * temperature as f32 / 65535.0,
+            humidity: Some(humidity.clamp(0.0, 100.0)),
+            pressure: None,
+        })
+    }
+}
+
+impl<HE, I: I2c<Error = HE>> TempHumSensor for Sht4x<HE, I> {
+    type Error = ShtError<HE>;
+
+    fn read_temp_hum(&mut self, mut delay: &mut dyn DelayUs) -> Result<TempHum, ShtError<HE>> {
+        self.read_measurement(&mut delay)
+    }
+}
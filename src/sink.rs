@@ -0,0 +1,161 @@
+#[cfg(feature = "display")]
+use crate::display::{self, Display};
+use crate::history::History;
+use crate::measurement::Measurement;
+use crate::mqtt::MqttPublisher;
+use crate::schedule::Schedule;
+use anyhow::Result;
+#[cfg(feature = "display")]
+use core::fmt;
+#[cfg(feature = "display")]
+use embedded_hal::i2c::I2c;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// An output the measurement of every cycle is handed to
+pub trait Sink {
+    /// Name of the output in the `outputs` setting and in warnings
+    fn name(&self) -> &'static str;
+
+    fn publish(&mut self, measurement: &Measurement) -> Result<()>;
+
+    /// Returns true if the output sends the measurement off the node, such
+    /// outputs are skipped in privacy mode
+    fn is_remote(&self) -> bool {
+        false
+    }
+}
+
+/// Hands the measurement to every enabled output when it is due, one failing
+/// output does not keep the others from publishing
+#[derive(Default)]
+pub struct Sinks<'a> {
+    sinks: Vec<(Box<dyn Sink + 'a>, Schedule)>,
+}
+
+impl<'a> Sinks<'a> {
+    /// Adds the output if it is listed in the comma separated `outputs`
+    pub fn add(&mut self, outputs: &str, sink: impl Sink + 'a) {
+        if outputs
+            .split(',')
+            .any(|output| output.trim() == sink.name())
+        {
+            log::info!("Output {} enabled", sink.name());
+            self.sinks
+                .push((Box::new(sink), Schedule::new(Duration::ZERO)));
+        }
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.sinks.iter().any(|(sink, _)| sink.name() == name)
+    }
+
+    /// Publishes to the output only every `interval` instead of every cycle
+    pub fn set_interval(&mut self, name: &str, interval: Duration) {
+        match self.sinks.iter_mut().find(|(sink, _)| sink.name() == name) {
+            Some((_, schedule)) => *schedule = Schedule::new(interval),
+            None => log::warn!("Interval for output {} that is not enabled", name),
+        }
+    }
+
+    pub fn publish(&mut self, measurement: &Measurement, local_only: bool) {
+        let now = Instant::now();
+        for (sink, schedule) in self
+            .sinks
+            .iter_mut()
+            .filter(|(sink, _)| !(local_only && sink.is_remote()))
+        {
+            if !schedule.is_due(now) {
+                continue;
+            }
+            if let Err(err) = sink.publish(measurement) {
+                log::warn!("error publishing to {}: {:}", sink.name(), err);
+            }
+        }
+    }
+}
+
+/// Logs every measurement, partial ones as warning
+pub struct LogSink;
+
+impl Sink for LogSink {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    fn publish(&mut self, measurement: &Measurement) -> Result<()> {
+        if measurement.is_partial() {
+            log::warn!("Partial measurement {:?}", measurement);
+        } else {
+            log::info!("Measurement {}", measurement);
+        }
+        Ok(())
+    }
+}
+
+/// Publishes the values to the `home/<location>/...` topics
+pub struct MqttSink {
+    client: MqttPublisher,
+    location: &'static str,
+    legacy_topics: bool,
+}
+
+impl MqttSink {
+    pub fn new(client: MqttPublisher, location: &'static str, legacy_topics: bool) -> Self {
+        Self {
+            client,
+            location,
+            legacy_topics,
+        }
+    }
+}
+
+impl Sink for MqttSink {
+    fn name(&self) -> &'static str {
+        "mqtt"
+    }
+
+    fn publish(&mut self, measurement: &Measurement) -> Result<()> {
+        crate::publish::publish_measurement(
+            &mut self.client,
+            self.location,
+            measurement,
+            self.legacy_topics,
+        );
+        Ok(())
+    }
+
+    fn is_remote(&self) -> bool {
+        true
+    }
+}
+
+/// Shows the measurement and the co2 trend of the history on the OLED display
+#[cfg(feature = "display")]
+pub struct DisplaySink<HE, I: I2c<Error = HE>> {
+    display: Display<HE, I>,
+    history: Arc<Mutex<History>>,
+}
+
+#[cfg(feature = "display")]
+impl<HE, I: I2c<Error = HE>> DisplaySink<HE, I> {
+    pub fn new(display: Display<HE, I>, history: Arc<Mutex<History>>) -> Self {
+        Self { display, history }
+    }
+}
+
+#[cfg(feature = "display")]
+impl<HE, I> Sink for DisplaySink<HE, I>
+where
+    HE: fmt::Debug + Send + Sync + 'static,
+    I: I2c<Error = HE>,
+{
+    fn name(&self) -> &'static str {
+        "display"
+    }
+
+    fn publish(&mut self, measurement: &Measurement) -> Result<()> {
+        let trend = self.history.lock().unwrap().co2_trend(display::TREND_LEN);
+        Ok(self.display.show(measurement, &trend)?)
+    }
+}
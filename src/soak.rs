@@ -0,0 +1,177 @@
+use crate::diagnostics::ReadTiming;
+use core::fmt;
+use std::{thread::sleep, time::Duration, time::Instant};
+
+/// Minimum sampling interval of the DHT22
+const DHT22_INTERVAL: Duration = Duration::from_secs(2);
+/// The MH-Z19 updates its measurement every 5 seconds
+const MHZ19_INTERVAL: Duration = Duration::from_secs(5);
+const REPORT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Upper bounds of the histogram buckets in milliseconds, the last bucket is open
+const BUCKETS_MS: [u128; 6] = [5, 10, 20, 50, 100, 500];
+
+/// Histogram of read durations
+#[derive(Debug, Default)]
+struct Histogram {
+    counts: [u32; BUCKETS_MS.len() + 1],
+}
+
+impl Histogram {
+    fn record(&mut self, duration: Duration) {
+        let millis = duration.as_millis();
+        let bucket = BUCKETS_MS
+            .iter()
+            .position(|bound| millis <= *bound)
+            .unwrap_or(BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+    }
+
+    fn to_json(&self) -> String {
+        let buckets = BUCKETS_MS
+            .iter()
+            .map(|bound| bound.to_string())
+            .chain(["inf".to_string()])
+            .zip(self.counts)
+            .map(|(bound, count)| format!("\"{}\": {}", bound, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{{{}}}", buckets)
+    }
+}
+
+/// Read statistics of one sensor during the soak test
+#[derive(Debug, Default)]
+struct SensorStats {
+    timing: ReadTiming,
+    histogram: Histogram,
+}
+
+impl SensorStats {
+    fn measure<T, E: fmt::Display>(&mut self, name: &str, read: impl FnOnce() -> Result<T, E>) {
+        if let Err(err) = self.timing.measure(read) {
+            log::warn!("soak test {} read failed: {}", name, err);
+        }
+        self.histogram.record(self.timing.last());
+    }
+
+    fn to_json(&self) -> String {
+        let success_rate = if self.timing.reads() == 0 {
+            0.0
+        } else {
+            100.0 * (self.timing.reads() - self.timing.failures()) as f32
+                / self.timing.reads() as f32
+        };
+        format!(
+            "{{\"success_rate\": {:.2}, \"timing\": {}, \"histogram_ms\": {}}}",
+            success_rate,
+            self.timing,
+            self.histogram.to_json()
+        )
+    }
+}
+
+fn free_heap() -> u32 {
+    unsafe { esp_idf_svc::sys::esp_get_free_heap_size() }
+}
+
+fn minimum_free_heap() -> u32 {
+    unsafe { esp_idf_svc::sys::esp_get_minimum_free_heap_size() }
+}
+
+/// The free heap shrank during the soak test, the sampling path leaks memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapDrop {
+    pub baseline: u32,
+    pub free: u32,
+}
+
+impl fmt::Display for HeapDrop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "free heap dropped from {} to {} bytes",
+            self.baseline, self.free
+        )
+    }
+}
+
+impl std::error::Error for HeapDrop {}
+
+/// Reads both sensors at their maximum safe rates and periodically passes a
+/// statistics report to `publish`. The soak test runs until the next reboot,
+/// or fails once the free heap shrank below its value at the first report.
+pub fn run<T, E: fmt::Display, U, F: fmt::Display>(
+    mut read_dht22: impl FnMut() -> Result<T, E>,
+    mut read_mhz19: impl FnMut() -> Result<U, F>,
+    mut wifi_connected: impl FnMut() -> bool,
+    mut publish: impl FnMut(&str),
+) -> HeapDrop {
+    log::info!("Starting soak test");
+    let start = Instant::now();
+    let start_heap = free_heap();
+
+    let mut dht22 = SensorStats::default();
+    let mut mhz19 = SensorStats::default();
+    let mut connected = wifi_connected();
+    let mut disconnects = 0;
+    let mut reconnects = 0;
+
+    let mut next_dht22 = start;
+    let mut next_mhz19 = start;
+    let mut next_report = start + REPORT_INTERVAL;
+    // free heap at the first report, the sampling path must not allocate so
+    // the heap may not shrink below it afterwards
+    let mut heap_baseline = None;
+    loop {
+        let now = Instant::now();
+        if now >= next_dht22 {
+            dht22.measure("dht22", &mut read_dht22);
+            next_dht22 += DHT22_INTERVAL;
+        }
+        if now >= next_mhz19 {
+            mhz19.measure("mhz19", &mut read_mhz19);
+            next_mhz19 += MHZ19_INTERVAL;
+        }
+
+        match (connected, wifi_connected()) {
+            (true, false) => {
+                disconnects += 1;
+                connected = false;
+            }
+            (false, true) => {
+                reconnects += 1;
+                connected = true;
+            }
+            _ => {}
+        }
+
+        if now >= next_report {
+            let free = free_heap();
+            let baseline = *heap_baseline.get_or_insert(free);
+            let heap_stable = free >= baseline;
+            let report = format!(
+                "{{\"uptime_s\": {}, \"dht22\": {}, \"mhz19\": {}, \"heap\": {{\"start\": {}, \"baseline\": {}, \"free\": {}, \"min_free\": {}, \"stable\": {}}}, \"wifi_disconnects\": {}, \"wifi_reconnects\": {}}}",
+                start.elapsed().as_secs(),
+                dht22.to_json(),
+                mhz19.to_json(),
+                start_heap,
+                baseline,
+                free,
+                minimum_free_heap(),
+                heap_stable,
+                disconnects,
+                reconnects
+            );
+            log::info!("Soak test report: {}", report);
+            publish(&report);
+            if !heap_stable {
+                return HeapDrop { baseline, free };
+            }
+            next_report += REPORT_INTERVAL;
+        }
+
+        let next = next_dht22.min(next_mhz19).min(next_report);
+        sleep(next.saturating_duration_since(Instant::now()));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::measurement::Measurement;
+#[cfg(feature = "std")]
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+#[cfg(feature = "std")]
+use esp_idf_svc::sys::EspError;
+
+#[cfg(feature = "std")]
+const NAMESPACE: &str = "spool";
+/// position of the oldest record in the lower and the number of records in
+/// the upper byte
+#[cfg(feature = "std")]
+const INDEX_KEY: &str = "index";
+/// The records share the nvs partition with the configuration
+#[cfg(feature = "std")]
+const MAX_RECORDS: usize = 128;
+/// timestamp, co2, temperature, humidity, pressure and the validity flags
+const RECORD_LEN: usize = 25;
+
+const VALID_TIMESTAMP: u8 = 0x01;
+const VALID_CO2: u8 = 0x02;
+const VALID_TEMPERATURE: u8 = 0x04;
+const VALID_HUMIDITY: u8 = 0x08;
+const VALID_PRESSURE: u8 = 0x10;
+
+/// Unsent measurements the history had to drop, kept in the nvs so they
+/// survive longer outages and restarts. The oldest record is dropped once
+/// the spool is full. Every record has its own key in a ring of slots, so a
+/// push only writes the new record and the index.
+#[cfg(feature = "std")]
+pub struct Spool {
+    nvs: EspNvs<NvsDefault>,
+    capacity: usize,
+    /// slot of the oldest record
+    head: usize,
+    len: usize,
+}
+
+#[cfg(feature = "std")]
+impl Spool {
+    pub fn new(partition: EspDefaultNvsPartition, capacity: usize) -> Result<Self, EspError> {
+        let nvs = EspNvs::new(partition, NAMESPACE, true)?;
+        let index = nvs.get_u32(INDEX_KEY)?.unwrap_or_default() as usize;
+        Ok(Self {
+            nvs,
+            capacity: capacity.clamp(1, MAX_RECORDS),
+            head: (index & 0xff) % MAX_RECORDS,
+            len: (index >> 8).min(MAX_RECORDS),
+        })
+    }
+
+    pub fn push(&mut self, measurement: &Measurement) -> Result<(), EspError> {
+        // the capacity may have been lowered since the records were stored
+        let dropped = (self.len + 1).saturating_sub(self.capacity);
+        let head = (self.head + dropped) % MAX_RECORDS;
+        let len = self.len - dropped;
+        self.nvs
+            .set_blob(&slot_key(head + len), &encode(measurement))?;
+        self.set_index(head, len + 1)
+    }
+
+    /// Removes and returns up to `max` of the oldest measurements
+    pub fn take(&mut self, max: usize) -> Result<Vec<Measurement>, EspError> {
+        let count = self.len.min(max);
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let mut taken = Vec::with_capacity(count);
+        let mut record = [0u8; RECORD_LEN];
+        for slot in self.head..self.head + count {
+            // a missing or partial record cannot be decoded
+            if let Some(RECORD_LEN) = self
+                .nvs
+                .get_blob(&slot_key(slot), &mut record)?
+                .map(|stored| stored.len())
+            {
+                taken.push(decode(&record));
+            }
+        }
+        self.set_index((self.head + count) % MAX_RECORDS, self.len - count)?;
+        Ok(taken)
+    }
+
+    fn set_index(&mut self, head: usize, len: usize) -> Result<(), EspError> {
+        self.nvs.set_u32(INDEX_KEY, (head | len << 8) as u32)?;
+        self.head = head;
+        self.len = len;
+        Ok(())
+    }
+}
+
+/// Returns the key of the slot, slots past the last one wrap around
+#[cfg(feature = "std")]
+fn slot_key(slot: usize) -> String {
+    format!("m{}", slot % MAX_RECORDS)
+}
+
+fn encode(measurement: &Measurement) -> [u8; RECORD_LEN] {
+    let mut record = [0u8; RECORD_LEN];
+    record[..8].copy_from_slice(&measurement.timestamp.unwrap_or_default().to_le_bytes());
+    record[8..12].copy_from_slice(&measurement.co2_ppm.to_le_bytes());
+    record[12..16].copy_from_slice(&measurement.temperature_c.to_le_bytes());
+    record[16..20].copy_from_slice(&measurement.humidity_pct.to_le_bytes());
+    record[20..24].copy_from_slice(&measurement.pressure_hpa.to_le_bytes());
+    for (valid, flag) in [
+        (measurement.timestamp.is_some(), VALID_TIMESTAMP),
+        (measurement.co2_valid, VALID_CO2),
+        (measurement.temperature_valid, VALID_TEMPERATURE),
+        (measurement.humidity_valid, VALID_HUMIDITY),
+        (measurement.pressure_valid, VALID_PRESSURE),
+    ] {
+        if valid {
+            record[24] |= flag;
+        }
+    }
+    record
+}
+
+fn decode(record: &[u8]) -> Measurement {
+    let bytes = |range: core::ops::Range<usize>| -> [u8; 4] { record[range].try_into().unwrap() };
+    let flags = record[24];
+    let timestamp = u64::from_le_bytes(record[..8].try_into().unwrap());
+    Measurement {
+        timestamp: (flags & VALID_TIMESTAMP != 0).then_some(timestamp),
+        co2_ppm: i32::from_le_bytes(bytes(8..12)),
+        temperature_c: f32::from_le_bytes(bytes(12..16)),
+        humidity_pct: f32::from_le_bytes(bytes(16..20)),
+        pressure_hpa: f32::from_le_bytes(bytes(20..24)),
+        co2_valid: flags & VALID_CO2 != 0,
+        temperature_valid: flags & VALID_TEMPERATURE != 0,
+        humidity_valid: flags & VALID_HUMIDITY != 0,
+        pressure_valid: flags & VALID_PRESSURE != 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_round_trip() {
+        let mut measurement = Measurement::new(Some(1_704_110_400));
+        measurement.set_co2(612);
+        measurement.set_temperature(21.4);
+        measurement.set_humidity(48.2);
+        measurement.set_pressure(1013.2);
+        assert_eq!(decode(&encode(&measurement)), measurement);
+    }
+
+    #[test]
+    fn record_keeps_the_invalid_values_invalid() {
+        let mut measurement = Measurement::new(None);
+        measurement.set_temperature(-3.5);
+        let decoded = decode(&encode(&measurement));
+        assert_eq!(decoded, measurement);
+        assert_eq!(decoded.timestamp, None);
+        assert_eq!(decoded.co2(), None);
+    }
+}
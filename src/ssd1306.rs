@@ -0,0 +1,156 @@
+use core::convert::Infallible;
+use core::fmt;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Pixel, Size};
+use embedded_hal::i2c::{I2c, Operation};
+
+pub const ADDRESS: u8 = 0x3c;
+const WIDTH: usize = 128;
+const PAGES: usize = 8;
+
+/// control bytes announcing a command stream or display data
+const CONTROL_COMMAND: u8 = 0x00;
+const CONTROL_DATA: u8 = 0x40;
+
+#[derive(Debug)]
+pub enum OledError<HE> {
+    /// Error of underlying IO
+    HalError(HE),
+}
+
+impl<HE> From<HE> for OledError<HE> {
+    fn from(error: HE) -> Self {
+        OledError::HalError(error)
+    }
+}
+
+impl<HE: fmt::Debug> fmt::Display for OledError<HE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OledError::HalError(err) => write!(f, "HAL error: {:?}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<HE: fmt::Debug> std::error::Error for OledError<HE> {}
+
+/// The controller of the module, both drive a 128x64 pixel display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    Ssd1306,
+    /// has 132 columns of ram with the visible ones starting at column 2
+    Sh1106,
+}
+
+impl Controller {
+    fn column_offset(self) -> u8 {
+        match self {
+            Controller::Ssd1306 => 0,
+            Controller::Sh1106 => 2,
+        }
+    }
+}
+
+/// Monochrome 128x64 OLED display, drawing goes to a frame buffer which is
+/// sent to the display by `flush`
+pub struct Oled<HE, I: I2c<Error = HE>> {
+    i2c: I,
+    controller: Controller,
+    buffer: [u8; WIDTH * PAGES],
+}
+
+impl<HE, I: I2c<Error = HE>> Oled<HE, I> {
+    pub fn new(i2c: I, controller: Controller) -> Self {
+        Self {
+            i2c,
+            controller,
+            buffer: [0; WIDTH * PAGES],
+        }
+    }
+
+    /// Configures the controller and switches the display on
+    pub fn init(&mut self) -> Result<(), OledError<HE>> {
+        let charge_pump: &[u8] = match self.controller {
+            Controller::Ssd1306 => &[0x8d, 0x14],
+            Controller::Sh1106 => &[0xad, 0x8b],
+        };
+        // display off, clock divider, 64 lines multiplex, no offset, start line 0
+        self.command(&[0xae, 0xd5, 0x80, 0xa8, 0x3f, 0xd3, 0x00, 0x40])?;
+        self.command(charge_pump)?;
+        // mirrored segments and com scan so (0, 0) is top left, com pin layout,
+        // contrast, pre-charge period, vcomh level
+        self.command(&[0xa1, 0xc8, 0xda, 0x12, 0x81, 0xcf, 0xd9, 0xf1, 0xdb, 0x40])?;
+        // show the ram content not inverted and switch the display on
+        self.command(&[0xa4, 0xa6, 0xaf])?;
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.fill(0);
+    }
+
+    /// Sends the frame buffer page by page, page addressing is supported by
+    /// both controllers
+    pub fn flush(&mut self) -> Result<(), OledError<HE>> {
+        let column = self.controller.column_offset();
+        for (page, data) in self.buffer.chunks(WIDTH).enumerate() {
+            let start = [
+                CONTROL_COMMAND,
+                0xb0 | page as u8,
+                column & 0x0f,
+                0x10 | column >> 4,
+            ];
+            self.i2c.write(ADDRESS, &start)?;
+            // adjacent writes are sent without a repeated start
+            self.i2c.transaction(
+                ADDRESS,
+                &mut [Operation::Write(&[CONTROL_DATA]), Operation::Write(data)],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, commands: &[u8]) -> Result<(), OledError<HE>> {
+        self.i2c.transaction(
+            ADDRESS,
+            &mut [
+                Operation::Write(&[CONTROL_COMMAND]),
+                Operation::Write(commands),
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+impl<HE, I: I2c<Error = HE>> OriginDimensions for Oled<HE, I> {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, (PAGES * 8) as u32)
+    }
+}
+
+impl<HE, I: I2c<Error = HE>> DrawTarget for Oled<HE, I> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<P>(&mut self, pixels: P) -> Result<(), Self::Error>
+    where
+        P: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            // pixels outside of the display are clipped
+            let (x, y) = match (usize::try_from(point.x), usize::try_from(point.y)) {
+                (Ok(x), Ok(y)) if x < WIDTH && y < PAGES * 8 => (x, y),
+                _ => continue,
+            };
+            // every byte holds a column of 8 pixels of a page, lsb on top
+            let index = y / 8 * WIDTH + x;
+            let bit = 1 << (y % 8);
+            match color {
+                BinaryColor::On => self.buffer[index] |= bit,
+                BinaryColor::Off => self.buffer[index] &= !bit,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use crate::bme280::Bme280Error;
+use crate::dht22::DhtError;
+use crate::mh_z19::MHz19Error;
+use crate::scd::ScdError;
+use crate::schedule::Schedule;
+use crate::sht::ShtError;
+use core::fmt;
+use esp_idf_svc::hal::i2c::I2cError;
+use esp_idf_svc::hal::io::EspIOError;
+use esp_idf_svc::sys::EspError;
+use std::time::{Duration, Instant};
+
+/// Result of a single sensor read as counted by the health statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOutcome {
+    Success,
+    /// the data was corrupted on the wire, e.g. by a long or loose DHT22 cable
+    Checksum,
+    /// the sensor did not answer in time
+    Timeout,
+    Other,
+}
+
+impl ReadOutcome {
+    /// Classifies the result of a read by the error of the sensor driver
+    pub fn of<T>(result: &anyhow::Result<T>) -> Self {
+        let Err(err) = result else {
+            return Self::Success;
+        };
+        if let Some(err) = err.downcast_ref::<DhtError<EspError>>() {
+            return match err {
+                DhtError::CheckSum(..) => Self::Checksum,
+                DhtError::NotFoundOnGPio | DhtError::ReadTimeout => Self::Timeout,
+                _ => Self::Other,
+            };
+        }
+        if let Some(err) = err.downcast_ref::<MHz19Error<EspIOError>>() {
+            return match err {
+                MHz19Error::Checksum(..) => Self::Checksum,
+                MHz19Error::Timeout => Self::Timeout,
+                _ => Self::Other,
+            };
+        }
+        if let Some(ScdError::Crc(..)) = err.downcast_ref::<ScdError<I2cError>>() {
+            return Self::Checksum;
+        }
+        if let Some(ShtError::Crc(..)) = err.downcast_ref::<ShtError<I2cError>>() {
+            return Self::Checksum;
+        }
+        if let Some(Bme280Error::Busy) = err.downcast_ref::<Bme280Error<I2cError>>() {
+            return Self::Timeout;
+        }
+        Self::Other
+    }
+}
+
+/// Error counters of a single sensor since the start
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SensorHealth {
+    pub successes: u32,
+    pub checksum_failures: u32,
+    pub timeouts: u32,
+    pub other_failures: u32,
+    /// failed reads since the last successful one
+    pub failure_streak: u32,
+    pub longest_failure_streak: u32,
+    /// the sensor failed too often in a row and is only probed for recovery
+    pub faulted: bool,
+}
+
+impl SensorHealth {
+    pub fn record(&mut self, outcome: ReadOutcome) {
+        match outcome {
+            ReadOutcome::Success => {
+                self.successes += 1;
+                self.failure_streak = 0;
+                return;
+            }
+            ReadOutcome::Checksum => self.checksum_failures += 1,
+            ReadOutcome::Timeout => self.timeouts += 1,
+            ReadOutcome::Other => self.other_failures += 1,
+        }
+        self.failure_streak += 1;
+        self.longest_failure_streak = self.longest_failure_streak.max(self.failure_streak);
+    }
+}
+
+impl fmt::Display for SensorHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{\"successes\": {}, \"checksum_failures\": {}, \"timeouts\": {}, \"other_failures\": {}, \"failure_streak\": {}, \"longest_failure_streak\": {}, \"faulted\": {}}}",
+            self.successes,
+            self.checksum_failures,
+            self.timeouts,
+            self.other_failures,
+            self.failure_streak,
+            self.longest_failure_streak,
+            self.faulted
+        )
+    }
+}
+
+/// Marks a sensor as faulted after a streak of failed reads. A faulted sensor
+/// is only read at the probe interval until a read succeeds again, instead of
+/// retrying and logging a warning every cycle. A threshold of 0 never marks
+/// the sensor as faulted.
+pub struct FaultDetector {
+    threshold: u32,
+    probe: Schedule,
+    /// state last returned by `update`, none before the first read
+    reported: Option<bool>,
+}
+
+impl FaultDetector {
+    pub fn new(threshold: u32, probe_interval: Duration) -> Self {
+        Self {
+            threshold,
+            probe: Schedule::new(probe_interval),
+            reported: None,
+        }
+    }
+
+    /// Returns true if the sensor is to be read in this cycle
+    pub fn should_read(&mut self, health: &SensorHealth, now: Instant) -> bool {
+        !health.faulted || self.probe.is_due(now)
+    }
+
+    /// Updates the fault flag after a read, returns the flag after the first
+    /// read and whenever it changed
+    pub fn update(&mut self, health: &mut SensorHealth) -> Option<bool> {
+        if health.failure_streak == 0 {
+            health.faulted = false;
+        } else if self.threshold > 0 && health.failure_streak >= self.threshold && !health.faulted {
+            health.faulted = true;
+            // the first probe follows one probe interval after the fault
+            self.probe.restart(Instant::now());
+        }
+        if self.reported == Some(health.faulted) {
+            return None;
+        }
+        self.reported = Some(health.faulted);
+        Some(health.faulted)
+    }
+}
+
+/// Health of the co2 and the climate sensor, to spot a node with a flaky
+/// sensor or wiring among several
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stats {
+    pub co2: SensorHealth,
+    pub climate: SensorHealth,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{\"co2\": {}, \"climate\": {}}}",
+            self.co2, self.climate
+        )
+    }
+}
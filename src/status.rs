@@ -0,0 +1,260 @@
+use crate::diagnostics::Diagnostics;
+use crate::history::History;
+use crate::measurement::Measurement;
+use crate::payload::LabelValue;
+use crate::sensors::RetryStats;
+use crate::stats::Stats;
+use crate::wifi::ScanResult;
+use anyhow::Result;
+use embedded_svc::http::Method;
+use embedded_svc::io::Write;
+use esp_idf_svc::http::server::{Configuration, EspHttpServer};
+use esp_idf_svc::sys;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// State of the node shown by the status endpoint, updated every cycle
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Status {
+    pub measurement: Option<Measurement>,
+    pub diagnostics: Diagnostics,
+    pub dht22_retries: RetryStats,
+    pub wifi_reconnects: u32,
+    pub privacy_mode: bool,
+    pub stats: Stats,
+}
+
+/// Serves the latest status as json on `GET /status` and as Prometheus
+/// metrics on `GET /metrics`, the recent measurements on `GET /history`
+pub struct StatusServer {
+    status: Arc<Mutex<Status>>,
+    _server: EspHttpServer<'static>,
+}
+
+impl StatusServer {
+    pub fn start(
+        boot_time: Instant,
+        location: &'static str,
+        history: Arc<Mutex<History>>,
+        access_points: Vec<ScanResult>,
+    ) -> Result<Self> {
+        let status = Arc::new(Mutex::new(Status::default()));
+        let mut server = EspHttpServer::new(&Configuration::default())?;
+        let shared = status.clone();
+        server.fn_handler("/status", Method::Get, move |request| {
+            let status = *shared.lock().unwrap();
+            let json = to_json(
+                &status,
+                boot_time.elapsed().as_secs(),
+                rssi(),
+                &access_points,
+            );
+            request
+                .into_response(200, None, &[("Content-Type", "application/json")])?
+                .write_all(json.as_bytes())?;
+            Ok(())
+        })?;
+        let shared = status.clone();
+        server.fn_handler("/metrics", Method::Get, move |request| {
+            let status = *shared.lock().unwrap();
+            let metrics = to_metrics(&status, location, boot_time.elapsed().as_secs(), rssi());
+            request
+                .into_response(200, None, &[("Content-Type", "text/plain; version=0.0.4")])?
+                .write_all(metrics.as_bytes())?;
+            Ok(())
+        })?;
+        let shared = status.clone();
+        server.fn_handler("/history", Method::Get, move |request| {
+            // the measurements are left out in privacy mode
+            let json = if shared.lock().unwrap().privacy_mode {
+                "[]".to_string()
+            } else {
+                history.lock().unwrap().to_json()
+            };
+            request
+                .into_response(200, None, &[("Content-Type", "application/json")])?
+                .write_all(json.as_bytes())?;
+            Ok(())
+        })?;
+        Ok(Self {
+            status,
+            _server: server,
+        })
+    }
+
+    pub fn update(&self, status: Status) {
+        *self.status.lock().unwrap() = status;
+    }
+}
+
+/// Signal strength of the access point in dBm, none while not connected
+fn rssi() -> Option<i8> {
+    let mut ap_info = sys::wifi_ap_record_t::default();
+    let result = unsafe { sys::esp_wifi_sta_get_ap_info(&mut ap_info) };
+    (result == sys::ESP_OK).then_some(ap_info.rssi)
+}
+
+/// The measurement is left out in privacy mode
+fn to_json(
+    status: &Status,
+    uptime_s: u64,
+    rssi: Option<i8>,
+    access_points: &[ScanResult],
+) -> String {
+    let measurement = match status.measurement {
+        Some(measurement) if !status.privacy_mode => measurement.to_string(),
+        _ => "null".into(),
+    };
+    let rssi = match rssi {
+        Some(rssi) => rssi.to_string(),
+        None => "null".into(),
+    };
+    let access_points = access_points
+        .iter()
+        .map(ScanResult::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "{{\"firmware\": \"{}\", \"uptime_s\": {}, \"privacy_mode\": {}, \"measurement\": {}, \"wifi\": {{\"rssi_dbm\": {}, \"reconnects\": {}, \"access_points\": [{}]}}, \"errors\": {{\"dht22\": {}, \"dht22_retries\": {{\"reads\": {}, \"retries\": {}, \"failures\": {}}}, \"mhz19\": {}}}, \"stats\": {}, \"memory\": {}}}",
+        env!("CARGO_PKG_VERSION"),
+        uptime_s,
+        status.privacy_mode,
+        measurement,
+        rssi,
+        status.wifi_reconnects,
+        access_points,
+        status.diagnostics.dht22,
+        status.dht22_retries.reads,
+        status.dht22_retries.retries,
+        status.dht22_retries.failures,
+        status.diagnostics.mhz19,
+        status.stats,
+        status.diagnostics.memory
+    )
+}
+
+/// Formats the status in the Prometheus text exposition format, the sensor
+/// values are left out in privacy mode
+fn to_metrics(status: &Status, location: &str, uptime_s: u64, rssi: Option<i8>) -> String {
+    let mut metrics = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: &dyn core::fmt::Display| {
+        let _ = write!(
+            metrics,
+            "# HELP {0} {1}\n# TYPE {0} {2}\n{0}{{location=\"{3}\"}} {4}\n",
+            name,
+            help,
+            kind,
+            LabelValue(location),
+            value
+        );
+    };
+
+    if let (Some(measurement), false) = (status.measurement, status.privacy_mode) {
+        if let Some(co2) = measurement.co2() {
+            metric("co2_ppm", "gauge", "CO2 concentration in ppm", &co2);
+        }
+        if let Some(temperature) = measurement.temperature() {
+            metric(
+                "temperature_celsius",
+                "gauge",
+                "Ambient temperature",
+                &temperature,
+            );
+        }
+        if let Some(humidity) = measurement.humidity() {
+            metric("humidity_percent", "gauge", "Relative humidity", &humidity);
+        }
+        if let Some(pressure) = measurement.pressure() {
+            metric("pressure_hpa", "gauge", "Air pressure", &pressure);
+        }
+    }
+    metric("uptime_seconds", "gauge", "Time since the start", &uptime_s);
+    if let Some(rssi) = rssi {
+        metric(
+            "wifi_rssi_dbm",
+            "gauge",
+            "Signal strength of the access point",
+            &rssi,
+        );
+    }
+    metric(
+        "wifi_reconnects_total",
+        "counter",
+        "Reconnect attempts after the access point dropped the connection",
+        &status.wifi_reconnects,
+    );
+    for (sensor, timing) in [
+        ("dht22", &status.diagnostics.dht22),
+        ("mhz19", &status.diagnostics.mhz19),
+    ] {
+        metric(
+            &format!("{}_reads_total", sensor),
+            "counter",
+            "Sensor reads",
+            &timing.reads(),
+        );
+        metric(
+            &format!("{}_failures_total", sensor),
+            "counter",
+            "Failed sensor reads",
+            &timing.failures(),
+        );
+        metric(
+            &format!("{}_timeouts_total", sensor),
+            "counter",
+            "Sensor reads exceeding the protocol timing",
+            &timing.timeouts(),
+        );
+    }
+    for (sensor, health) in [
+        ("co2", &status.stats.co2),
+        ("climate", &status.stats.climate),
+    ] {
+        metric(
+            &format!("{}_checksum_failures_total", sensor),
+            "counter",
+            "Sensor reads with corrupted data",
+            &health.checksum_failures,
+        );
+        metric(
+            &format!("{}_failure_streak", sensor),
+            "gauge",
+            "Failed sensor reads since the last successful one",
+            &health.failure_streak,
+        );
+        metric(
+            &format!("{}_faulted", sensor),
+            "gauge",
+            "1 while the sensor is only probed after repeated failures",
+            &u8::from(health.faulted),
+        );
+    }
+    metric(
+        "dht22_retries_total",
+        "counter",
+        "Reads of the DHT repeated after an error",
+        &status.dht22_retries.retries,
+    );
+    let memory = &status.diagnostics.memory;
+    metric("free_heap_bytes", "gauge", "Free heap", &memory.free_heap);
+    metric(
+        "min_free_heap_bytes",
+        "gauge",
+        "Lowest free heap since the start",
+        &memory.min_free_heap,
+    );
+    metric(
+        "largest_free_block_bytes",
+        "gauge",
+        "Largest free block of the heap",
+        &memory.largest_block,
+    );
+    metric(
+        "stack_high_water_bytes",
+        "gauge",
+        "Lowest free stack of the main task since the start",
+        &memory.stack_high_water,
+    );
+    metrics
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use embedded_hal::digital::{OutputPin, PinState};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Health of the node shown by the status led
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedState {
+    Connecting,
+    MqttDown,
+    SensorError,
+    Co2High,
+    Ok,
+}
+
+impl LedState {
+    const ALL: [LedState; 5] = [
+        LedState::Connecting,
+        LedState::MqttDown,
+        LedState::SensorError,
+        LedState::Co2High,
+        LedState::Ok,
+    ];
+
+    /// Picks the state to show, the most basic problem wins
+    pub fn from_health(
+        wifi_connected: bool,
+        mqtt_connected: bool,
+        sensor_error: bool,
+        co2_high: bool,
+    ) -> Self {
+        if !wifi_connected {
+            LedState::Connecting
+        } else if !mqtt_connected {
+            LedState::MqttDown
+        } else if sensor_error {
+            LedState::SensorError
+        } else if co2_high {
+            LedState::Co2High
+        } else {
+            LedState::Ok
+        }
+    }
+
+    /// Durations in ms of the alternating on and off phases of one period
+    fn pattern(self) -> &'static [u64] {
+        match self {
+            // fast blinking
+            LedState::Connecting => &[100, 100],
+            // double flash
+            LedState::MqttDown => &[100, 150, 100, 1150],
+            // triple flash
+            LedState::SensorError => &[100, 150, 100, 150, 100, 900],
+            // slow blinking
+            LedState::Co2High => &[1000, 1000],
+            // short heartbeat
+            LedState::Ok => &[50, 2950],
+        }
+    }
+}
+
+/// Blinks the pattern of the current state on the led in a thread of its own
+pub struct StatusLed {
+    state: Arc<AtomicU8>,
+}
+
+impl StatusLed {
+    pub fn start<P: OutputPin + Send + 'static>(mut pin: P) -> Result<Self> {
+        let state = Arc::new(AtomicU8::new(LedState::Connecting as u8));
+        let shown = state.clone();
+        thread::Builder::new()
+            .name("status-led".into())
+            .stack_size(2048)
+            .spawn(move || loop {
+                // a new state is shown from the next period on
+                let state = LedState::ALL[shown.load(Ordering::Relaxed) as usize];
+                for (phase, duration) in state.pattern().iter().enumerate() {
+                    let _ = pin.set_state(PinState::from(phase % 2 == 0));
+                    thread::sleep(Duration::from_millis(*duration));
+                }
+            })?;
+        Ok(Self { state })
+    }
+
+    pub fn set(&self, state: LedState) {
+        let previous = self.state.swap(state as u8, Ordering::Relaxed);
+        if previous != state as u8 {
+            log::info!("Status led shows {:?}", state);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use esp_idf_svc::http::client::Configuration;
+use esp_idf_svc::mqtt::client::MqttClientConfiguration;
+use esp_idf_svc::sys::{self, esp, EspError};
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// How the MQTT client and the InfluxDB output verify the certificate of the
+/// server of a TLS connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ServerVerification {
+    /// the root certificates of public CAs bundled with esp-idf
+    #[default]
+    Bundle,
+    /// the configured CA certificate, e.g. of a private broker
+    CaCert,
+}
+
+impl ServerVerification {
+    /// Installs the PEM encoded CA certificate for all TLS connections that
+    /// use the global CA store, without certificate the bundle is used
+    pub fn init(ca_cert: &str) -> Self {
+        if ca_cert.is_empty() {
+            return Self::Bundle;
+        }
+        match set_global_ca_store(ca_cert) {
+            Ok(()) => Self::CaCert,
+            Err(err) => {
+                log::warn!("error installing the CA certificate: {:}", err);
+                Self::Bundle
+            }
+        }
+    }
+
+    pub fn configure_mqtt(self, mqtt_config: &mut MqttClientConfiguration) {
+        match self {
+            Self::Bundle => mqtt_config.crt_bundle_attach = Some(sys::esp_crt_bundle_attach),
+            Self::CaCert => mqtt_config.use_global_ca_store = true,
+        }
+    }
+
+    pub fn http_configuration(self) -> Configuration {
+        match self {
+            Self::Bundle => Configuration {
+                crt_bundle_attach: Some(sys::esp_crt_bundle_attach),
+                ..Default::default()
+            },
+            Self::CaCert => Configuration {
+                use_global_ca_store: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+fn set_global_ca_store(pem: &str) -> Result<(), EspError> {
+    // esp-tls expects PEM data terminated by a nul character, it keeps a copy
+    let pem = format!("{}\0", pem);
+    esp!(unsafe { sys::esp_tls_set_global_ca_store(pem.as_ptr(), pem.len() as u32) })
+}
+
+/// Restores the line breaks of a PEM certificate pasted on a single line of
+/// the console, returns none if the value is no certificate
+pub fn normalize_pem(value: &str) -> Option<String> {
+    let body = value
+        .trim()
+        .strip_prefix(PEM_BEGIN)?
+        .strip_suffix(PEM_END)?;
+    let base64: String = body.split_whitespace().collect();
+    if base64.is_empty() {
+        return None;
+    }
+    let mut pem = String::with_capacity(base64.len() + base64.len() / 64 + 64);
+    pem.push_str(PEM_BEGIN);
+    pem.push('\n');
+    for line in base64.as_bytes().chunks(64) {
+        pem.push_str(core::str::from_utf8(line).ok()?);
+        pem.push('\n');
+    }
+    pem.push_str(PEM_END);
+    pem.push('\n');
+    Some(pem)
+}
@@ -0,0 +1,50 @@
+use esp_idf_hal::delay;
+use esp_idf_hal::io::EspIOError;
+use esp_idf_hal::uart::UartDriver;
+
+/// UART driver which tells whether received bytes are waiting, so drivers can
+/// read with a timeout instead of blocking forever
+pub struct PolledUart<'d> {
+    uart: UartDriver<'d>,
+}
+
+impl<'d> PolledUart<'d> {
+    pub fn new(uart: UartDriver<'d>) -> Self {
+        Self { uart }
+    }
+}
+
+impl embedded_io::ErrorType for PolledUart<'_> {
+    type Error = EspIOError;
+}
+
+impl embedded_io::Read for PolledUart<'_> {
+    /// Blocks only until a byte is received. The read of the driver waits until
+    /// the whole buffer is filled, so at most the received bytes are read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let received = self.uart.remaining_read().map_err(EspIOError)?;
+        let len = buf.len().min(received.max(1));
+        self.uart
+            .read(&mut buf[..len], delay::BLOCK)
+            .map_err(EspIOError)
+    }
+}
+
+impl embedded_io::ReadReady for PolledUart<'_> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.uart.remaining_read().map_err(EspIOError)? > 0)
+    }
+}
+
+impl embedded_io::Write for PolledUart<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        embedded_io::Write::write(&mut self.uart, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        embedded_io::Write::flush(&mut self.uart)
+    }
+}
@@ -0,0 +1,51 @@
+/// Decodes a value of an `application/x-www-form-urlencoded` body, a `%`
+/// that does not start an escape of two hex digits is kept as it is
+pub fn decode(value: &str) -> String {
+    let input = value.as_bytes();
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut pos = 0;
+    while pos < input.len() {
+        match input[pos] {
+            b'+' => bytes.push(b' '),
+            b'%' => match (hex_digit(input.get(pos + 1)), hex_digit(input.get(pos + 2))) {
+                (Some(high), Some(low)) => {
+                    bytes.push(high << 4 | low);
+                    pos += 2;
+                }
+                _ => bytes.push(b'%'),
+            },
+            byte => bytes.push(byte),
+        }
+        pos += 1;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn hex_digit(byte: Option<&u8>) -> Option<u8> {
+    (*byte? as char).to_digit(16).map(|digit| digit as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_escapes_and_spaces() {
+        assert_eq!(decode("my+wifi%21%c3%a4"), "my wifi!ä");
+        assert_eq!(decode("100%25"), "100%");
+    }
+
+    #[test]
+    fn keeps_invalid_escapes() {
+        assert_eq!(decode("50%+off"), "50% off");
+        assert_eq!(decode("%zzabc"), "%zzabc");
+        assert_eq!(decode("%4xyz"), "%4xyz");
+        assert_eq!(decode("%+f"), "% f");
+    }
+
+    #[test]
+    fn keeps_truncated_escapes() {
+        assert_eq!(decode("abc%"), "abc%");
+        assert_eq!(decode("abc%4"), "abc%4");
+    }
+}
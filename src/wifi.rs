@@ -1,48 +1,415 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
+use core::fmt;
+use embedded_svc::ipv4::{self, ClientSettings, DHCPClientSettings, Mask, Subnet};
 use embedded_svc::wifi::{AuthMethod, ClientConfiguration, Configuration};
 use esp_idf_hal::peripheral;
-use esp_idf_svc::{eventloop::EspSystemEventLoop, wifi::BlockingWifi, wifi::EspWifi};
+use esp_idf_svc::eventloop::{EspSystemEventLoop, EspSystemSubscription};
+use esp_idf_svc::netif::{EspNetif, IpEvent, NetifConfiguration, NetifStack};
+use esp_idf_svc::sys::{
+    esp, esp_wifi_connect, esp_wifi_get_config, esp_wifi_set_config, esp_wifi_sta_wpa2_ent_enable,
+    esp_wifi_sta_wpa2_ent_set_identity, esp_wifi_sta_wpa2_ent_set_password,
+    esp_wifi_sta_wpa2_ent_set_username, wifi_config_t, wifi_interface_t_WIFI_IF_STA, EspError,
+};
+use esp_idf_svc::wifi::{BlockingWifi, EspWifi, WifiDriver, WifiEvent};
 use log::{info, warn};
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
 
-pub fn wifi(
-    ssid: &str,
-    pass: &str,
+/// Wait before the first reconnect attempt, doubled after every failed attempt
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Failed attempts before the station moves on to the next configured network
+const NETWORK_ATTEMPTS: u32 = 3;
+
+/// Name and password of a network the station may join
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    pub ssid: String,
+    pub pass: String,
+}
+
+/// Authentication of the station at the access point
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum WifiAuth {
+    /// network without password
+    Open,
+    #[default]
+    Wpa2Personal,
+    Wpa3Personal,
+    /// PEAP or TTLS with the user name and the wifi password, the identity is
+    /// sent unencrypted and defaults to the user name. The certificate of the
+    /// authentication server is not validated.
+    Wpa2Enterprise {
+        identity: String,
+        username: String,
+    },
+}
+
+impl WifiAuth {
+    /// Parses the `wifi_auth` setting, `open`, `wpa2`, `wpa3` or `enterprise`
+    pub fn from_setting(name: &str, identity: &str, username: &str) -> Option<Self> {
+        match name {
+            "open" => Some(Self::Open),
+            "wpa2" => Some(Self::Wpa2Personal),
+            "wpa3" => Some(Self::Wpa3Personal),
+            "enterprise" => Some(Self::Wpa2Enterprise {
+                identity: identity.into(),
+                username: username.into(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn method(&self) -> AuthMethod {
+        match self {
+            Self::Open => AuthMethod::None,
+            Self::Wpa2Personal => AuthMethod::WPA2Personal,
+            Self::Wpa3Personal => AuthMethod::WPA3Personal,
+            Self::Wpa2Enterprise { .. } => AuthMethod::WPA2Enterprise,
+        }
+    }
+}
+
+/// Access point of the configured network found by the scan before connecting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanResult {
+    pub bssid: [u8; 6],
+    pub channel: u8,
+    pub rssi: i8,
+}
+
+/// Formats the access point as json
+impl fmt::Display for ScanResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.bssid;
+        write!(
+            f,
+            "{{\"bssid\": \"{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}\", \"channel\": {}, \"rssi_dbm\": {}}}",
+            a, b, c, d, e, g, self.channel, self.rssi
+        )
+    }
+}
+
+/// Addresses of the station, by default everything is assigned by DHCP
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IpSettings {
+    /// fixed address and prefix length instead of a DHCP lease
+    pub static_ip: Option<(Ipv4Addr, u8)>,
+    /// required with a fixed address
+    pub gateway: Option<Ipv4Addr>,
+    pub dns: Option<Ipv4Addr>,
+    /// name the node registers with the DHCP server
+    pub hostname: Option<String>,
+}
+
+impl IpSettings {
+    fn configuration(&self) -> Result<ipv4::ClientConfiguration> {
+        match (self.static_ip, self.gateway) {
+            (Some((ip, prefix)), Some(gateway)) => {
+                Ok(ipv4::ClientConfiguration::Fixed(ClientSettings {
+                    ip,
+                    subnet: Subnet {
+                        gateway,
+                        mask: Mask(prefix),
+                    },
+                    dns: self.dns,
+                    secondary_dns: None,
+                }))
+            }
+            (Some(_), None) => bail!("a static ip needs a gateway"),
+            (None, _) => Ok(ipv4::ClientConfiguration::DHCP(DHCPClientSettings {
+                hostname: match &self.hostname {
+                    Some(hostname) => Some(
+                        hostname
+                            .parse()
+                            .map_err(|_| anyhow!("hostname too long: {}", hostname))?,
+                    ),
+                    None => None,
+                },
+            })),
+        }
+    }
+}
+
+/// Creates the wifi driver with the station addresses configured as given
+pub fn station(
     modem: impl peripheral::Peripheral<P = esp_idf_hal::modem::Modem> + 'static,
     sysloop: EspSystemEventLoop,
+    ip: &IpSettings,
 ) -> Result<Box<EspWifi<'static>>> {
-    let auth_method = AuthMethod::WPA2Personal;
-    if ssid.is_empty() {
+    let sta_netif = EspNetif::new_with_conf(&NetifConfiguration {
+        ip_configuration: ipv4::Configuration::Client(ip.configuration()?),
+        ..NetifConfiguration::wifi_default_client()
+    })?;
+    Ok(Box::new(EspWifi::wrap_all(
+        WifiDriver::new(modem, sysloop, None)?,
+        sta_netif,
+        EspNetif::new(NetifStack::Ap)?,
+    )?))
+}
+
+/// Connects the station to the first of the given networks that accepts it,
+/// the networks are tried in turn with a few attempts each. Without a limit of
+/// attempts it retries until the connection is established. With `scan` the
+/// station is bound to the access point of the network with the strongest
+/// signal, the access points found are returned strongest first.
+pub fn connect(
+    esp_wifi: &mut EspWifi<'static>,
+    networks: &[Network],
+    auth: &WifiAuth,
+    scan: bool,
+    max_attempts: Option<u32>,
+    sysloop: EspSystemEventLoop,
+) -> Result<Vec<ScanResult>> {
+    let networks: Vec<&Network> = networks
+        .iter()
+        .filter(|network| !network.ssid.is_empty())
+        .collect();
+    if networks.is_empty() {
         bail!("Missing WiFi name")
     }
-    if pass.is_empty() {
-        bail!("Wifi password is empty");
+    for network in &networks {
+        if network.pass.is_empty() && *auth != WifiAuth::Open {
+            bail!("Wifi password of {} is empty", network.ssid);
+        }
+        if network.ssid.len() > 32 || network.pass.len() > 64 {
+            bail!("Wifi name or password of {} too long", network.ssid);
+        }
     }
-    let mut esp_wifi = EspWifi::new(modem, sysloop.clone(), None)?;
-    let mut wifi = BlockingWifi::wrap(&mut esp_wifi, sysloop)?;
+    let mut wifi = BlockingWifi::wrap(esp_wifi, sysloop)?;
 
-    let config = Configuration::Client(ClientConfiguration {
-        ssid: ssid.into(),
-        bssid: None,
-        auth_method,
-        password: pass.into(),
-        channel: None,
-    });
-    wifi.set_configuration(&config)?;
+    configure(&mut wifi, networks[0], auth, None)?;
+    if let WifiAuth::Wpa2Enterprise { identity, username } = auth {
+        if username.is_empty() {
+            bail!("Wifi user name is empty");
+        }
+        let identity = if identity.is_empty() {
+            username
+        } else {
+            identity
+        };
+        esp!(unsafe {
+            esp_wifi_sta_wpa2_ent_set_identity(identity.as_ptr(), identity.len() as i32)
+        })?;
+        esp!(unsafe {
+            esp_wifi_sta_wpa2_ent_set_username(username.as_ptr(), username.len() as i32)
+        })?;
+        esp!(unsafe { esp_wifi_sta_wpa2_ent_enable() })?;
+    }
 
     info!("Starting wifi...");
     wifi.start()?;
 
     info!("Connecting wifi...");
-    while let Err(e) = wifi.connect() {
-        warn!("Could not connect to wifi {}", e);
-        info!("Retrying!");
-    }
+    let mut attempts = 0;
+    let access_points = 'connect: loop {
+        for network in &networks {
+            let access_points = if scan {
+                scan_network(&mut wifi, &network.ssid)
+            } else {
+                Vec::new()
+            };
+            configure(&mut wifi, network, auth, access_points.first())?;
+            for _ in 0..NETWORK_ATTEMPTS {
+                match wifi.connect() {
+                    Ok(()) => break 'connect access_points,
+                    Err(e) => warn!("Could not connect to wifi {} {}", network.ssid, e),
+                }
+                attempts += 1;
+                if max_attempts.is_some_and(|max_attempts| attempts >= max_attempts) {
+                    wifi.stop()?;
+                    bail!("no wifi connection after {} attempts", attempts);
+                }
+                info!("Retrying!");
+            }
+        }
+    };
 
-    info!("Waiting for DHCP lease...");
+    info!("Waiting for the network interface...");
     wifi.wait_netif_up()?;
 
     let ip_info = wifi.wifi().sta_netif().get_ip_info()?;
-    info!("Wifi DHCP info: {:?}", ip_info);
+    info!("Wifi ip info: {:?}", ip_info);
+
+    Ok(access_points)
+}
+
+/// Sets the network the station connects to, bound to the given access point
+fn configure(
+    wifi: &mut BlockingWifi<&mut EspWifi<'static>>,
+    network: &Network,
+    auth: &WifiAuth,
+    access_point: Option<&ScanResult>,
+) -> Result<()> {
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration {
+        ssid: network.ssid.as_str().into(),
+        bssid: access_point.map(|ap| ap.bssid),
+        auth_method: auth.method(),
+        // the enterprise password is handed to the supplicant instead
+        password: match auth {
+            WifiAuth::Open | WifiAuth::Wpa2Enterprise { .. } => "".into(),
+            _ => network.pass.as_str().into(),
+        },
+        channel: access_point.map(|ap| ap.channel),
+    }))?;
+    if let WifiAuth::Wpa2Enterprise { .. } = auth {
+        set_enterprise_password(&network.pass)?;
+    }
+    Ok(())
+}
+
+fn set_enterprise_password(pass: &str) -> Result<(), EspError> {
+    esp!(unsafe { esp_wifi_sta_wpa2_ent_set_password(pass.as_ptr(), pass.len() as i32) })
+}
+
+/// Returns the access points of the network, strongest first
+fn scan_network(wifi: &mut BlockingWifi<&mut EspWifi<'static>>, ssid: &str) -> Vec<ScanResult> {
+    info!("Scanning wifi...");
+    let mut access_points: Vec<ScanResult> = match wifi.scan() {
+        Ok(found) => found
+            .into_iter()
+            .filter(|ap| ap.ssid == ssid)
+            .map(|ap| ScanResult {
+                bssid: ap.bssid,
+                channel: ap.channel,
+                rssi: ap.signal_strength,
+            })
+            .collect(),
+        Err(err) => {
+            warn!("error scanning wifi: {:}", err);
+            Vec::new()
+        }
+    };
+    access_points.sort_by_key(|ap| core::cmp::Reverse(ap.rssi));
+    match access_points.first() {
+        Some(strongest) => info!("Using access point {}", strongest),
+        None => warn!("No access point of {} found", ssid),
+    }
+    access_points
+}
+
+/// Moves the station on from the network it is configured for to the next one
+/// of the list, the binding to an access point of the scan is dropped
+fn next_network<'a>(networks: &'a [Network], auth: &WifiAuth) -> Result<&'a Network, EspError> {
+    let mut config = wifi_config_t::default();
+    esp!(unsafe { esp_wifi_get_config(wifi_interface_t_WIFI_IF_STA, &mut config) })?;
+    let sta = unsafe { &mut config.sta };
+    let ssid = sta.ssid.split(|b| *b == 0).next().unwrap_or_default();
+    let current = networks
+        .iter()
+        .position(|network| network.ssid.as_bytes() == ssid)
+        .unwrap_or(0);
+    let network = &networks[(current + 1) % networks.len()];
+
+    sta.ssid = [0; 32];
+    sta.ssid[..network.ssid.len()].copy_from_slice(network.ssid.as_bytes());
+    sta.password = [0; 64];
+    if let WifiAuth::Wpa2Personal | WifiAuth::Wpa3Personal = auth {
+        sta.password[..network.pass.len()].copy_from_slice(network.pass.as_bytes());
+    }
+    sta.bssid_set = false;
+    sta.channel = 0;
+    esp!(unsafe { esp_wifi_set_config(wifi_interface_t_WIFI_IF_STA, &mut config) })?;
+    if let WifiAuth::Wpa2Enterprise { .. } = auth {
+        set_enterprise_password(&network.pass)?;
+    }
+    Ok(network)
+}
+
+#[derive(Default)]
+struct WifiState {
+    connected: AtomicBool,
+    reconnects: AtomicU32,
+}
+
+/// Reconnects the station whenever the access point drops it, e.g. after a
+/// reboot of the access point, and tells the rest of the firmware whether the
+/// node is connected
+pub struct WifiSupervisor {
+    state: Arc<WifiState>,
+    _wifi_events: EspSystemSubscription<'static>,
+    _ip_events: EspSystemSubscription<'static>,
+}
+
+impl WifiSupervisor {
+    /// Starts supervising a connected station, after a few failed reconnects
+    /// it moves on to the next of the networks
+    pub fn start(
+        sysloop: &EspSystemEventLoop,
+        networks: Vec<Network>,
+        auth: WifiAuth,
+    ) -> Result<Self> {
+        let state = Arc::new(WifiState::default());
+        state.connected.store(true, Ordering::Relaxed);
+
+        let (disconnected, disconnects) = mpsc::channel();
+        let wifi_state = state.clone();
+        let wifi_events = sysloop.subscribe::<WifiEvent, _>(move |event| {
+            if *event == WifiEvent::StaDisconnected {
+                wifi_state.connected.store(false, Ordering::Relaxed);
+                let _ = disconnected.send(());
+            }
+        })?;
+        // the connection is usable once the station got its address
+        let ip_state = state.clone();
+        let ip_events = sysloop.subscribe::<IpEvent, _>(move |event| {
+            if let IpEvent::DhcpIpAssigned(_) = event {
+                ip_state.connected.store(true, Ordering::Relaxed);
+            }
+        })?;
 
-    Ok(Box::new(esp_wifi))
+        let supervisor_state = state.clone();
+        thread::Builder::new()
+            .name("wifi".into())
+            .stack_size(4096)
+            .spawn(move || {
+                let state = supervisor_state;
+                while disconnects.recv().is_ok() {
+                    warn!("Wifi connection lost");
+                    let mut backoff = MIN_BACKOFF;
+                    let mut attempts = 0;
+                    while !state.connected.load(Ordering::Relaxed) {
+                        thread::sleep(backoff);
+                        if state.connected.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        if networks.len() > 1 && attempts > 0 && attempts % NETWORK_ATTEMPTS == 0 {
+                            match next_network(&networks, &auth) {
+                                Ok(network) => info!("Switching to wifi {}", network.ssid),
+                                Err(err) => warn!("error switching wifi network: {:}", err),
+                            }
+                        }
+                        attempts += 1;
+                        info!("Reconnecting wifi...");
+                        state.reconnects.fetch_add(1, Ordering::Relaxed);
+                        if let Err(err) = esp!(unsafe { esp_wifi_connect() }) {
+                            warn!("Could not reconnect wifi {}", err);
+                        }
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                    info!("Wifi reconnected");
+                    // failed attempts leave a disconnect event each
+                    while disconnects.try_recv().is_ok() {}
+                }
+            })?;
+
+        Ok(Self {
+            state,
+            _wifi_events: wifi_events,
+            _ip_events: ip_events,
+        })
+    }
+
+    /// Returns true while the station is connected and has an address
+    pub fn is_connected(&self) -> bool {
+        self.state.connected.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of reconnect attempts since the start
+    pub fn reconnects(&self) -> u32 {
+        self.state.reconnects.load(Ordering::Relaxed)
+    }
 }
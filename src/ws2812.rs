@@ -0,0 +1,75 @@
+use core::time::Duration;
+use esp_idf_svc::hal::rmt::{FixedLengthSignal, PinState, Pulse, TxRmtDriver};
+use esp_idf_svc::sys::EspError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const OFF: Rgb = Rgb::new(0, 0, 0);
+    pub const GREEN: Rgb = Rgb::new(0, 255, 0);
+    pub const YELLOW: Rgb = Rgb::new(255, 160, 0);
+    pub const RED: Rgb = Rgb::new(255, 0, 0);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Scales the color to the given brightness out of 255
+    fn dimmed(self, brightness: u8) -> Self {
+        let dim = |value: u8| (value as u16 * brightness as u16 / 255) as u8;
+        Self::new(dim(self.r), dim(self.g), dim(self.b))
+    }
+}
+
+/// A single WS2812 (NeoPixel) led driven by the RMT peripheral
+pub struct Ws2812<'d> {
+    tx: TxRmtDriver<'d>,
+    brightness: u8,
+}
+
+impl<'d> Ws2812<'d> {
+    /// The RMT channel has to run with a clock divider of 1 for the timing
+    pub fn new(tx: TxRmtDriver<'d>, brightness: u8) -> Self {
+        Self { tx, brightness }
+    }
+
+    pub fn set(&mut self, color: Rgb) -> Result<(), EspError> {
+        let color = color.dimmed(self.brightness);
+        // the led expects green, red and blue with the msb first
+        let grb = (color.g as u32) << 16 | (color.r as u32) << 8 | color.b as u32;
+        let ticks_hz = self.tx.counter_clock()?;
+        let zero = (
+            Pulse::new_with_duration(ticks_hz, PinState::High, &Duration::from_nanos(350))?,
+            Pulse::new_with_duration(ticks_hz, PinState::Low, &Duration::from_nanos(800))?,
+        );
+        let one = (
+            Pulse::new_with_duration(ticks_hz, PinState::High, &Duration::from_nanos(700))?,
+            Pulse::new_with_duration(ticks_hz, PinState::Low, &Duration::from_nanos(600))?,
+        );
+        let mut signal = FixedLengthSignal::<24>::new();
+        for bit in 0..24 {
+            let pulses = if grb & (1 << (23 - bit)) != 0 {
+                &one
+            } else {
+                &zero
+            };
+            signal.set(bit, pulses)?;
+        }
+        self.tx.start_blocking(&signal)
+    }
+}
+
+/// Traffic light color of the co2 concentration, off without a reading
+pub fn traffic_light(co2: Option<i32>, warn_ppm: i32, alert_ppm: i32) -> Rgb {
+    match co2 {
+        Some(co2) if co2 >= alert_ppm => Rgb::RED,
+        Some(co2) if co2 >= warn_ppm => Rgb::YELLOW,
+        Some(_) => Rgb::GREEN,
+        None => Rgb::OFF,
+    }
+}